@@ -1,6 +1,7 @@
-use spacetimedb::{table, reducer, ReducerContext, Identity, Table, Timestamp};
+use spacetimedb::{table, reducer, ReducerContext, Identity, ScheduleAt, Table, Timestamp};
 use spacetimedb::SpacetimeType;
 use spacetimedb::TimeDuration;
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 // Enums and shared types
@@ -36,13 +37,17 @@ pub enum PaymentMethod {
     Card,
     EWallet,
     Transfer,
+    Voucher,
 }
 
 #[derive(SpacetimeType, Clone, Debug, PartialEq)]
 pub enum POStatus {
     Created,
     Ordered,
+    PartiallyReceived,
     Received,
+    // closed before everything arrived; the remainder is no longer expected
+    ClosedShort,
     Cancelled,
 }
 
@@ -99,6 +104,160 @@ pub enum PromotionStatus {
     Ended,
 }
 
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum VoucherKind {
+    // carries a balance that can be spent over several sales
+    Balance,
+    // redeemed once for its full face value
+    SingleUse,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum VoucherStatus {
+    Active,
+    Redeemed,
+    Void,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum VoucherUsage {
+    Payment,
+    Discount,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum TableStatus {
+    Available,
+    Occupied,
+    OutOfService,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum ReservationStatus {
+    Booked,
+    Seated,
+    Completed,
+    Cancelled,
+    NoShow,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum DeliveryPlatform {
+    GoFood,
+    GrabFood,
+    ShopeeFood,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum SalesChannel {
+    DineIn,
+    Takeaway,
+    Delivery(DeliveryPlatform),
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum AvailabilityStatus {
+    Available,
+    // can still be sold, but an ingredient is below min_stock
+    Low,
+    SoldOut,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum StockMovementType {
+    Sale,
+    Purchase,
+    TransferIn,
+    TransferOut,
+    Waste,
+    Adjustment,
+    Count,
+    // raw ingredients consumed by a production order
+    ProductionOut,
+    // semi-finished ingredient produced by it
+    ProductionIn,
+    // accepted goods sent back to the supplier
+    PurchaseReturn,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum CountSessionStatus {
+    Open,
+    Review,
+    Approved,
+    Cancelled,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum WasteReason {
+    Expired,
+    Spoiled,
+    CookingError,
+    CustomerReturn,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum LotStatus {
+    Active,
+    NearExpiry,
+    Expired,
+    Depleted,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum UnitDimension {
+    Mass,
+    Volume,
+    Count,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum AlertSeverity {
+    // below min_stock
+    Warning,
+    // below half of min_stock
+    Critical,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum ProductionStatus {
+    Planned,
+    Completed,
+    Cancelled,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum TemperatureAlertKind {
+    OutOfRange,
+    MissedReading,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum InvoiceMatchStatus {
+    // invoice agrees with the PO and the goods received
+    Matched,
+    Mismatch,
+    // mismatch accepted by finance
+    Overridden,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum InvoicePaymentStatus {
+    Unpaid,
+    PartiallyPaid,
+    Paid,
+}
+
+#[derive(SpacetimeType, Clone, Debug, PartialEq)]
+pub enum PurchaseReturnResolution {
+    // waiting for the supplier
+    Pending,
+    // supplier delivers the goods again against the same PO
+    Replaced,
+    // supplier credits the amount
+    Credited,
+}
+
 // Input helper types
 
 #[derive(SpacetimeType, Clone, Debug)]
@@ -112,11 +271,102 @@ pub struct SaleItemInput {
 #[derive(SpacetimeType, Clone, Debug)]
 pub struct PurchaseOrderItemInput {
     pub ingredient_id: u64,
+    // purchase unit, e.g. kg or a 10 kg box
+    pub unit_id: u64,
+    // in unit_id
     pub quantity: i64,
-    // price per unit in cents
+    // price per unit_id in cents; None takes the supplier's catalog price
+    pub price: Option<i64>,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct SaleVoucherInput {
+    pub code: String,
+    pub usage: VoucherUsage,
+    // amount to redeem in cents; ignored for SingleUse vouchers
+    pub amount: i64,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct ExternalOrderItemInput {
+    // item id as known by the delivery platform
+    pub external_item_id: String,
+    pub quantity: i32,
+    // platform price per unit in cents
+    pub price: i64,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct ExternalOrderInput {
+    pub platform: DeliveryPlatform,
+    pub external_order_id: String,
+    pub outlet_id: u64,
+    pub items: Vec<ExternalOrderItemInput>,
+    // in cents
+    pub platform_total: i64,
+    pub commission: i64,
+    pub date: Timestamp,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct StockReceiptInput {
+    pub ingredient_id: u64,
+    pub unit_id: u64,
+    // in unit_id
+    pub quantity: i64,
+    pub lot_number: String,
+    pub expires_at: Option<Timestamp>,
+    // price per unit_id in cents
+    pub price: i64,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct ProductionInputLine {
+    pub ingredient_id: u64,
+    pub unit_id: u64,
+    // in unit_id
+    pub quantity: i64,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct GoodsReceiptLineInput {
+    pub po_item_id: u64,
+    // accepted into stock, in the item's ordered unit
+    pub received_quantity: i64,
+    // turned away at the door, in the item's ordered unit
+    pub rejected_quantity: i64,
+    pub rejection_reason: String,
+    // actual price per ordered unit in cents
+    pub price: i64,
+    pub lot_number: String,
+    pub expires_at: Option<Timestamp>,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct PurchaseOrderAmendmentLineInput {
+    // existing line to change, or None to add a line
+    pub po_item_id: Option<u64>,
+    // quantity 0 removes an existing line that has nothing received yet
+    pub item: PurchaseOrderItemInput,
+}
+
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct SupplierInvoiceLineInput {
+    pub po_item_id: u64,
+    // in the PO item's ordered unit
+    pub quantity: i64,
+    // invoiced price per ordered unit in cents
     pub price: i64,
 }
 
+#[derive(SpacetimeType, Clone, Debug)]
+pub struct PurchaseReturnLineInput {
+    pub grn_line_id: u64,
+    // in the PO item's ordered unit
+    pub quantity: i64,
+    pub reason: String,
+}
+
 // Tables
 
 #[table(name = outlets, public)]
@@ -155,7 +405,8 @@ pub struct Employee {
     pub status: EmploymentStatus,
 }
 
-#[table(name = products, public, index(name = prod_outlet_idx, btree(columns = [outlet_id])))]
+// Company-wide menu catalog; outlets enable items through outlet_menu_items.
+#[table(name = products, public)]
 #[derive(Clone)]
 pub struct Product {
     #[primary_key]
@@ -163,23 +414,91 @@ pub struct Product {
     pub id: u64,
     pub name: String,
     pub category: String,
-    // price in cents
+    // base price in cents
     pub price: i64,
+}
+
+#[table(
+    name = outlet_menu_items,
+    public,
+    index(name = menu_outlet_idx, btree(columns = [outlet_id])),
+    index(name = menu_product_idx, btree(columns = [product_id]))
+)]
+#[derive(Clone)]
+pub struct OutletMenuItem {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
     pub outlet_id: u64,
+    pub product_id: u64,
+    pub enabled: bool,
+    // in cents, takes precedence over the area price and the base price
+    pub price_override: Option<i64>,
+}
+
+#[table(name = area_menu_prices, public, index(name = areaprice_product_idx, btree(columns = [product_id])))]
+#[derive(Clone)]
+pub struct AreaMenuPrice {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub area: String,
+    pub product_id: u64,
+    // in cents
+    pub price: i64,
+}
+
+// Each dimension has one base unit (factor 1): g, ml and pcs. All stored
+// ingredient quantities are in the ingredient's base unit.
+#[table(name = units_of_measure, public)]
+#[derive(Clone)]
+pub struct UnitOfMeasure {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    #[unique]
+    pub code: String,
+    pub name: String,
+    pub dimension: UnitDimension,
+    // base units per one of this unit, e.g. 1000 for kg or 30 for a 30-egg tray
+    pub factor: i64,
 }
 
-#[table(name = ingredients, public, index(name = ing_outlet_idx, btree(columns = [outlet_id])))]
+// Company-wide ingredient master; stock is held per outlet in ingredient_stock.
+#[table(name = ingredients, public)]
 #[derive(Clone)]
 pub struct Ingredient {
     #[primary_key]
     #[auto_inc]
     pub id: u64,
+    #[unique]
+    pub sku: String,
     pub name: String,
-    pub unit: String,
+    pub category: String,
+    // base unit; all quantities of this ingredient are in this unit
+    pub unit_id: u64,
+    pub halal: bool,
+    pub status: IngredientStatus,
+}
+
+#[table(
+    name = ingredient_stock,
+    public,
+    index(name = stock_outlet_idx, btree(columns = [outlet_id])),
+    index(name = stock_ingredient_outlet_idx, btree(columns = [ingredient_id, outlet_id]))
+)]
+#[derive(Clone)]
+pub struct IngredientStock {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub ingredient_id: u64,
+    pub outlet_id: u64,
+    // in the ingredient's base unit
     pub min_stock: i64,
     pub stock: i64,
-    pub outlet_id: u64,
-    pub status: IngredientStatus,
+    // moving-average cost per base unit, in cents
+    pub avg_cost: i64,
 }
 
 #[table(name = suppliers, public)]
@@ -190,7 +509,12 @@ pub struct Supplier {
     pub id: u64,
     pub name: String,
     pub contact: String,
+    // 1-5 stars from the latest supplier_scores row; 0 until first scored
     pub rating: i32,
+    // days from invoice date to due date
+    pub payment_terms_days: u32,
+    // days from order to expected delivery
+    pub lead_time_days: u32,
 }
 
 #[table(name = sales, public, index(name = sales_outlet_time_idx, btree(columns = [outlet_id, date])))]
@@ -200,8 +524,13 @@ pub struct Sale {
     #[auto_inc]
     pub id: u64,
     pub outlet_id: u64,
-    // total in cents
+    pub channel: SalesChannel,
+    // total in cents, after discount
     pub total: i64,
+    // cost of goods sold in cents, from the ingredient movements of the sale
+    pub cogs: i64,
+    // discount in cents
+    pub discount: i64,
     pub payment_method: PaymentMethod,
     pub date: Timestamp,
 }
@@ -231,6 +560,11 @@ pub struct PurchaseOrder {
     pub total: i64,
     pub status: POStatus,
     pub date: Timestamp,
+    // order date plus the supplier's lead time
+    pub expected_delivery: Timestamp,
+    pub created_by: Identity,
+    // 0 as created; incremented by each amendment once ordered
+    pub revision: u32,
 }
 
 #[table(name = purchase_order_items, public, index(name = poi_po_idx, btree(columns = [purchase_order_id])))]
@@ -241,9 +575,20 @@ pub struct PurchaseOrderItem {
     pub id: u64,
     pub purchase_order_id: u64,
     pub ingredient_id: u64,
+    // unit the item was ordered in
+    pub unit_id: u64,
+    // in unit_id, the unit the price is quoted in
+    pub ordered_quantity: i64,
+    // the ordered quantity in the ingredient's base unit
     pub quantity: i64,
-    // price per unit in cents
+    // accepted on goods received notes so far, in the ingredient's base unit
+    pub received_quantity: i64,
+    // price per unit_id in cents
     pub price: i64,
+    // supplier catalog price on the PO date, if there is one
+    pub catalog_price: Option<i64>,
+    // price deviates from catalog_price by more than PRICE_TOLERANCE_BPS
+    pub price_flagged: bool,
 }
 
 #[table(name = distributions, public, index(name = dist_outlet_idx, btree(columns = [from_outlet_id, to_outlet_id])))]
@@ -255,6 +600,7 @@ pub struct Distribution {
     pub from_outlet_id: u64,
     pub to_outlet_id: u64,
     pub ingredient_id: u64,
+    // in the ingredient's base unit
     pub quantity: i64,
     pub status: DistributionStatus,
     pub date: Timestamp,
@@ -343,828 +689,5449 @@ pub struct CashFlow {
     pub description: String,
 }
 
-// Reducers
-
-#[reducer(init)]
-pub fn init(ctx: &ReducerContext) -> Result<(), String> {
-    // Time helpers
-    let now = ctx.timestamp;
-    let days = |d: i64| TimeDuration::from_duration(Duration::from_secs((d * 86400) as u64));
-    let hours = |h: i64| TimeDuration::from_duration(Duration::from_secs((h * 3600) as u64));
-    let safe_sub = |ts: Timestamp, dur: TimeDuration| ts.checked_sub(dur).unwrap_or(ts);
-    let safe_add = |ts: Timestamp, dur: TimeDuration| ts.checked_add(dur).unwrap_or(ts);
+#[table(name = voucher_batches, public)]
+#[derive(Clone)]
+pub struct VoucherBatch {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub name: String,
+    pub kind: VoucherKind,
+    // face value in cents
+    pub face_value: i64,
+    pub quantity: u32,
+    pub expires_at: Timestamp,
+    pub created_at: Timestamp,
+}
 
-    // 1) Outlets (18)
-    let outlet_names = [
-        "Kampoeng Steak Jakarta Pusat",
-        "Kampoeng Steak Jakarta Barat",
-        "Kampoeng Steak Tangerang",
-        "Kampoeng Steak Bekasi",
-        "Kampoeng Steak Bogor",
-        "Kampoeng Steak Depok",
-        "Kampoeng Steak Bandung",
-        "Kampoeng Steak Surabaya",
-        "Kampoeng Steak Semarang",
-        "Kampoeng Steak Yogyakarta",
-        "Kampoeng Steak Medan",
-        "Kampoeng Steak Palembang",
-        "Kampoeng Steak Makassar",
-        "Kampoeng Steak Bali",
-        "Kampoeng Steak Malang",
-        "Kampoeng Steak Solo",
-        "Kampoeng Steak Pekanbaru",
-        "Kampoeng Steak Balikpapan",
-    ];
-    for (i, name) in outlet_names.iter().enumerate() {
-        let area = name.split_whitespace().last().unwrap_or("Area").to_string();
-        let address = format!("Jl. Utama No. {}, {}", i + 1, area);
-        ctx.db.outlets().insert(Outlet {
-            id: 0,
-            name: name.to_string(),
-            area,
-            address,
-            status: OutletStatus::Open,
-        });
-    }
+// Not public: voucher codes are bearer credentials.
+#[table(name = vouchers, index(name = voucher_batch_idx, btree(columns = [batch_id])))]
+#[derive(Clone)]
+pub struct Voucher {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    #[unique]
+    pub code: String,
+    pub batch_id: u64,
+    pub kind: VoucherKind,
+    // in cents
+    pub face_value: i64,
+    pub balance: i64,
+    pub expires_at: Timestamp,
+    pub status: VoucherStatus,
+}
 
-    // Collect outlet ids
-    let outlet_ids: Vec<u64> = ctx.db.outlets().iter().map(|o| o.id).collect();
-    let outlet_count = outlet_ids.len();
+#[table(name = voucher_redemptions, public, index(name = redemption_voucher_idx, btree(columns = [voucher_id])))]
+#[derive(Clone)]
+pub struct VoucherRedemption {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub voucher_id: u64,
+    pub sale_id: u64,
+    pub outlet_id: u64,
+    pub usage: VoucherUsage,
+    // in cents
+    pub amount: i64,
+    // value of a SingleUse voucher left over after covering the amount due, in cents
+    pub forfeited: i64,
+    pub date: Timestamp,
+}
 
-    // 2) Suppliers (5)
-    let suppliers = [
-        ("PT Daging Prima", "021-5551234"),
-        ("PT Sayur Fresh", "021-5555678"),
-        ("PT Bumbu Nusantara", "021-7771111"),
-        ("PT Minuman Sejuk", "021-8882222"),
-        ("PT Kemasan Jaya", "021-9993333"),
-    ];
-    for (i, (name, contact)) in suppliers.iter().enumerate() {
-        ctx.db.suppliers().insert(Supplier {
-            id: 0,
-            name: (*name).to_string(),
-            contact: (*contact).to_string(),
-            rating: 4 + ((i % 2) as i32), // 4-5 stars
-        });
-    }
-    let supplier_ids: Vec<u64> = ctx.db.suppliers().iter().map(|s| s.id).collect();
+#[table(name = dining_tables, public, index(name = table_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct DiningTable {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub number: String,
+    pub capacity: u32,
+    // e.g. "Smoking", "Non-Smoking", "Outdoor"
+    pub area: String,
+    pub status: TableStatus,
+}
 
-    // 3) Products (10)
-    let products = [
-        ("Steak Wagyu", "Makanan", 185_000),
-        ("Steak Sirloin", "Makanan", 95_000),
-        ("Steak Tenderloin", "Makanan", 115_000),
-        ("Chicken Steak", "Makanan", 55_000),
-        ("Fish & Chips", "Makanan", 48_000),
-        ("Pasta Carbonara", "Makanan", 45_000),
-        ("French Fries", "Makanan", 20_000),
-        ("Orange Juice", "Minuman", 18_000),
-        ("Iced Tea", "Minuman", 10_000),
-        ("Coffee", "Minuman", 22_000),
-    ];
-    for (i, (name, cat, price_rp)) in products.iter().enumerate() {
-        let outlet_id = outlet_ids[i % outlet_count];
-        ctx.db.products().insert(Product {
-            id: 0,
-            name: (*name).to_string(),
-            category: (*cat).to_string(),
-            price: (*price_rp as i64) * 100,
-            outlet_id,
-        });
-    }
-    let product_ids: Vec<u64> = ctx.db.products().iter().map(|p| p.id).collect();
+#[table(
+    name = reservations,
+    public,
+    index(name = res_outlet_idx, btree(columns = [outlet_id])),
+    index(name = res_table_idx, btree(columns = [table_id]))
+)]
+#[derive(Clone)]
+pub struct Reservation {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub table_id: u64,
+    pub customer_name: String,
+    pub customer_phone: String,
+    pub party_size: u32,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub status: ReservationStatus,
+    pub created_at: Timestamp,
+}
 
-    // 4) Ingredients (20) distributed across outlets
-    let ingredients = [
-        ("Daging Sapi", "kg"),
-        ("Daging Ayam", "kg"),
-        ("Ikan", "kg"),
-        ("Kentang", "kg"),
-        ("Bawang", "kg"),
-        ("Tomat", "kg"),
-        ("Keju", "kg"),
-        ("Susu", "L"),
-        ("Telur", "pcs"),
-        ("Tepung", "kg"),
-        ("Minyak", "L"),
-        ("Garam", "kg"),
-        ("Merica", "kg"),
-        ("Saus", "L"),
-        ("Pasta", "kg"),
-        ("Kopi", "kg"),
-        ("Teh", "kg"),
-        ("Jus Jeruk", "L"),
-        ("Es Batu", "kg"),
-        ("Gula", "kg"),
+#[table(name = reservation_release_schedule, scheduled(release_no_show_reservations))]
+pub struct ReservationReleaseSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+#[table(name = platform_product_mappings, public, index(name = ppm_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct PlatformProductMapping {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub platform: DeliveryPlatform,
+    pub outlet_id: u64,
+    pub external_item_id: String,
+    pub product_id: u64,
+}
+
+#[table(name = external_orders, public, index(name = extorder_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct ExternalOrder {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    // "<platform>:<external order id>", guarantees each platform order is ingested once
+    #[unique]
+    pub external_key: String,
+    pub platform: DeliveryPlatform,
+    pub external_order_id: String,
+    pub outlet_id: u64,
+    pub sale_id: u64,
+    // in cents
+    pub platform_total: i64,
+    pub commission: i64,
+    // what the platform pays out after commission, in cents
+    pub net_receivable: i64,
+    pub received_at: Timestamp,
+}
+
+#[table(
+    name = recipes,
+    public,
+    index(name = recipe_product_idx, btree(columns = [product_id])),
+    index(name = recipe_ingredient_idx, btree(columns = [ingredient_id]))
+)]
+#[derive(Clone)]
+pub struct Recipe {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub product_id: u64,
+    pub ingredient_id: u64,
+    // quantity of the ingredient, in its base unit, per portion
+    pub quantity: i64,
+}
+
+#[table(name = product_availability, public, index(name = avail_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct ProductAvailability {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub product_id: u64,
+    // derived from ingredient stock and recipes
+    pub computed_status: AvailabilityStatus,
+    // set by a manager, takes precedence over computed_status
+    pub manual_status: Option<AvailabilityStatus>,
+    // effective status shown to POS clients
+    pub status: AvailabilityStatus,
+    // why computed_status is not Available
+    pub reason: String,
+    // the manager's note, shown instead of reason while manual_status is set
+    pub manual_reason: Option<String>,
+    pub updated_at: Timestamp,
+}
+
+#[table(
+    name = stock_movements,
+    public,
+    index(name = movement_ingredient_idx, btree(columns = [ingredient_id])),
+    index(name = movement_outlet_idx, btree(columns = [outlet_id]))
+)]
+#[derive(Clone)]
+pub struct StockMovement {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub ingredient_id: u64,
+    pub outlet_id: u64,
+    // signed change in the ingredient's unit
+    pub delta: i64,
+    pub balance_after: i64,
+    // cost per base unit and delta × unit_cost, in cents
+    pub unit_cost: i64,
+    pub value: i64,
+    pub movement_type: StockMovementType,
+    // source document, e.g. "sale:12" or "distribution:3"
+    pub reference: String,
+    pub reason: String,
+    // stock count that was in progress at the outlet when this was posted
+    pub count_session_id: Option<u64>,
+    pub created_by: Identity,
+    pub date: Timestamp,
+}
+
+#[table(name = stock_count_sessions, public, index(name = count_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct StockCountSession {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub status: CountSessionStatus,
+    pub started_by: Identity,
+    pub started_at: Timestamp,
+    pub approved_by: Option<Identity>,
+    pub approved_at: Option<Timestamp>,
+    // net value of the posted variances in cents
+    pub variance_value: i64,
+}
+
+#[table(name = stock_count_lines, public, index(name = countline_session_idx, btree(columns = [session_id])))]
+#[derive(Clone)]
+pub struct StockCountLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub session_id: u64,
+    pub ingredient_id: u64,
+    // system stock when the session started
+    pub system_qty: i64,
+    // sum of all count entries, None until counted
+    pub counted_qty: Option<i64>,
+    pub variance: i64,
+    // in cents, set when the count is approved
+    pub variance_value: i64,
+}
+
+// One row per counter and storage location, so several people can count the same ingredient.
+#[table(name = stock_count_entries, public, index(name = countentry_line_idx, btree(columns = [line_id])))]
+#[derive(Clone)]
+pub struct StockCountEntry {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub line_id: u64,
+    pub location: String,
+    pub quantity: i64,
+    pub counted_by: Identity,
+    pub counted_at: Timestamp,
+}
+
+// Exactly one of ingredient_id / product_id is set. Wasted products are
+// decremented through their recipe, one movement per ingredient.
+#[table(name = waste_records, public, index(name = waste_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct WasteRecord {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub ingredient_id: Option<u64>,
+    pub product_id: Option<u64>,
+    pub quantity: i64,
+    pub reason: WasteReason,
+    // value of the waste movements posted for it, in cents
+    pub cost: i64,
+    // cost entered by the person recording the waste, in cents
+    pub reported_cost: i64,
+    pub notes: String,
+    pub recorded_by: Identity,
+    pub date: Timestamp,
+}
+
+// Running waste totals per outlet and ingredient, including ingredients used by wasted products.
+#[table(name = waste_summary, public, index(name = wastesum_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct WasteSummary {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub ingredient_id: u64,
+    pub quantity: i64,
+    // in cents, at moving-average cost
+    pub value: i64,
+    pub incidents: u64,
+}
+
+#[table(
+    name = stock_lots,
+    public,
+    index(name = lot_ingredient_idx, btree(columns = [ingredient_id])),
+    index(name = lot_outlet_idx, btree(columns = [outlet_id])),
+    index(name = lot_number_idx, btree(columns = [lot_number]))
+)]
+#[derive(Clone)]
+pub struct StockLot {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub ingredient_id: u64,
+    pub outlet_id: u64,
+    // supplier batch number, kept when the lot is transferred to another outlet
+    pub lot_number: String,
+    pub supplier_id: Option<u64>,
+    // lot this one was transferred from
+    pub source_lot_id: Option<u64>,
+    pub quantity_received: i64,
+    pub quantity_remaining: i64,
+    // cost per base unit in cents
+    pub unit_cost: i64,
+    pub received_at: Timestamp,
+    pub expires_at: Option<Timestamp>,
+    pub status: LotStatus,
+}
+
+#[table(
+    name = lot_movements,
+    public,
+    index(name = lotmove_lot_idx, btree(columns = [lot_id])),
+    index(name = lotmove_movement_idx, btree(columns = [movement_id]))
+)]
+#[derive(Clone)]
+pub struct LotMovement {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub lot_id: u64,
+    pub movement_id: u64,
+    // signed, like StockMovement.delta
+    pub quantity: i64,
+}
+
+#[table(name = lot_expiry_schedule, scheduled(flag_expiring_lots))]
+pub struct LotExpirySchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+#[table(name = outlet_inventory_values, public)]
+#[derive(Clone)]
+pub struct OutletInventoryValue {
+    #[primary_key]
+    pub outlet_id: u64,
+    // sum of stock × avg_cost over the outlet's ingredient_stock rows, in cents
+    pub value: i64,
+    pub updated_at: Timestamp,
+}
+
+// One open alert (resolved_at = None) per ingredient while it stays below min_stock.
+#[table(
+    name = stock_alerts,
+    public,
+    index(name = alert_outlet_idx, btree(columns = [outlet_id])),
+    index(name = alert_ingredient_idx, btree(columns = [ingredient_id]))
+)]
+#[derive(Clone)]
+pub struct StockAlert {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub ingredient_id: u64,
+    pub outlet_id: u64,
+    pub severity: AlertSeverity,
+    pub stock: i64,
+    pub min_stock: i64,
+    pub acknowledged: bool,
+    pub acknowledged_by: Option<Identity>,
+    pub created_at: Timestamp,
+    pub resolved_at: Option<Timestamp>,
+}
+
+#[table(
+    name = reorder_rules,
+    public,
+    index(name = reorder_outlet_idx, btree(columns = [outlet_id])),
+    index(name = reorder_ingredient_idx, btree(columns = [ingredient_id]))
+)]
+#[derive(Clone)]
+pub struct ReorderRule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub ingredient_id: u64,
+    pub outlet_id: u64,
+    // in the ingredient's base unit; stock below par triggers a reorder
+    pub par_level: i64,
+    // in purchase_unit_id; 0 means order up to par
+    pub reorder_qty: i64,
+    pub purchase_unit_id: u64,
+    pub preferred_supplier_id: u64,
+}
+
+#[table(name = reorder_schedule, scheduled(run_reorder_job))]
+pub struct ReorderSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+// Turns raw ingredients into a semi-finished ingredient, e.g. 10 kg Daging Sapi into
+// 36 Sirloin 250g portions. Quantities are in base units.
+#[table(name = production_orders, public, index(name = production_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct ProductionOrder {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    pub output_ingredient_id: u64,
+    pub planned_quantity: i64,
+    // set on completion
+    pub actual_quantity: i64,
+    // value of the consumed inputs, in cents
+    pub total_cost: i64,
+    // total_cost / actual_quantity, per base unit of the output
+    pub unit_cost: i64,
+    pub lot_id: Option<u64>,
+    pub status: ProductionStatus,
+    pub created_by: Identity,
+    pub created_at: Timestamp,
+    pub completed_at: Option<Timestamp>,
+}
+
+#[table(name = production_inputs, public, index(name = prodinput_order_idx, btree(columns = [order_id])))]
+#[derive(Clone)]
+pub struct ProductionInput {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub order_id: u64,
+    pub ingredient_id: u64,
+    // in the ingredient's base unit
+    pub quantity: i64,
+    // in cents at moving-average cost, set on completion
+    pub cost: i64,
+}
+
+// Theoretical usage (sales × recipe, plus production inputs) against actual usage
+// derived from the ledger, per outlet and period.
+#[table(name = usage_variance_reports, public, index(name = usagerep_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct UsageVarianceReport {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub outlet_id: u64,
+    // movements dated after period_start, up to and including period_end
+    pub period_start: Timestamp,
+    pub period_end: Timestamp,
+    // stock count whose approval closed the period, if any
+    pub count_session_id: Option<u64>,
+    // in cents
+    pub theoretical_value: i64,
+    pub actual_value: i64,
+    pub variance_value: i64,
+    pub generated_by: Identity,
+    pub generated_at: Timestamp,
+}
+
+// Quantities are in the ingredient's base unit. Outflows are positive.
+#[table(name = usage_variance_lines, public, index(name = usageline_report_idx, btree(columns = [report_id])))]
+#[derive(Clone)]
+pub struct UsageVarianceLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub report_id: u64,
+    pub ingredient_id: u64,
+    pub opening: i64,
+    // purchases and production output
+    pub received: i64,
+    pub transferred_in: i64,
+    pub transferred_out: i64,
+    pub closing: i64,
+    // opening + received + transferred_in - transferred_out - closing
+    pub actual: i64,
+    pub theoretical: i64,
+    pub waste: i64,
+    // actual - theoretical - waste; positive means unexplained loss
+    pub variance: i64,
+    // variance at the average cost of the period's outbound movements, in cents
+    pub variance_value: i64,
+}
+
+// HACCP settings for a chiller or freezer asset. Temperatures are in tenths of a degree Celsius.
+#[table(name = temperature_monitors, public)]
+#[derive(Clone)]
+pub struct TemperatureMonitor {
+    #[primary_key]
+    pub asset_id: u64,
+    pub outlet_id: u64,
+    pub min_temperature: i32,
+    pub max_temperature: i32,
+    pub interval_minutes: u64,
+    // consecutive out-of-range readings before the asset is put into Maintenance
+    pub persist_readings: u32,
+    pub consecutive_out_of_range: u32,
+    pub next_due_at: Timestamp,
+    pub active: bool,
+}
+
+#[table(
+    name = temperature_logs,
+    public,
+    index(name = templog_asset_idx, btree(columns = [asset_id])),
+    index(name = templog_outlet_idx, btree(columns = [outlet_id]))
+)]
+#[derive(Clone)]
+pub struct TemperatureLog {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub asset_id: u64,
+    pub outlet_id: u64,
+    // tenths of a degree Celsius
+    pub temperature: i32,
+    pub in_range: bool,
+    pub recorded_by: Identity,
+    pub recorded_at: Timestamp,
+}
+
+// Scheduled readings nobody logged within the grace period.
+#[table(name = missed_temperature_checks, public, index(name = missedtemp_outlet_idx, btree(columns = [outlet_id])))]
+#[derive(Clone)]
+pub struct MissedTemperatureCheck {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub asset_id: u64,
+    pub outlet_id: u64,
+    pub due_at: Timestamp,
+    pub flagged_at: Timestamp,
+}
+
+// At most one open alert per asset and kind.
+#[table(
+    name = temperature_alerts,
+    public,
+    index(name = tempalert_outlet_idx, btree(columns = [outlet_id])),
+    index(name = tempalert_asset_idx, btree(columns = [asset_id]))
+)]
+#[derive(Clone)]
+pub struct TemperatureAlert {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub asset_id: u64,
+    pub outlet_id: u64,
+    pub kind: TemperatureAlertKind,
+    // last out-of-range reading, in tenths of a degree
+    pub temperature: Option<i32>,
+    pub acknowledged: bool,
+    pub acknowledged_by: Option<Identity>,
+    pub created_at: Timestamp,
+    pub resolved_at: Option<Timestamp>,
+}
+
+#[table(name = temperature_check_schedule, scheduled(flag_missed_temperature_checks))]
+pub struct TemperatureCheckSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+#[table(name = goods_received_notes, public, index(name = grn_po_idx, btree(columns = [purchase_order_id])))]
+#[derive(Clone)]
+pub struct GoodsReceivedNote {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub purchase_order_id: u64,
+    pub outlet_id: u64,
+    pub supplier_id: u64,
+    // accepted quantity × actual price, in cents
+    pub total: i64,
+    pub notes: String,
+    pub received_by: Identity,
+    pub date: Timestamp,
+}
+
+// Quantities are in the ingredient's base unit; price is per ordered unit.
+#[table(
+    name = goods_received_lines,
+    public,
+    index(name = grnline_grn_idx, btree(columns = [grn_id])),
+    index(name = grnline_poitem_idx, btree(columns = [po_item_id]))
+)]
+#[derive(Clone)]
+pub struct GoodsReceivedLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub grn_id: u64,
+    pub po_item_id: u64,
+    pub ingredient_id: u64,
+    pub unit_id: u64,
+    pub received_quantity: i64,
+    pub rejected_quantity: i64,
+    pub rejection_reason: String,
+    pub price: i64,
+    // Purchase movement posted for the accepted quantity
+    pub movement_id: Option<u64>,
+}
+
+// Changes to a PO after it was ordered. Each amendment bumps PurchaseOrder.revision.
+#[table(name = purchase_order_amendments, public, index(name = poamend_po_idx, btree(columns = [purchase_order_id])))]
+#[derive(Clone)]
+pub struct PurchaseOrderAmendment {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub purchase_order_id: u64,
+    // revision the PO is at after this amendment
+    pub revision: u32,
+    pub reason: String,
+    // in cents
+    pub previous_total: i64,
+    pub new_total: i64,
+    pub amended_by: Identity,
+    pub date: Timestamp,
+}
+
+// Quantities are in the ingredient's base unit; 0 on one side means the line was added or removed.
+#[table(
+    name = purchase_order_amendment_lines,
+    public,
+    index(name = poamendline_amendment_idx, btree(columns = [amendment_id]))
+)]
+#[derive(Clone)]
+pub struct PurchaseOrderAmendmentLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub amendment_id: u64,
+    pub po_item_id: u64,
+    pub ingredient_id: u64,
+    pub previous_quantity: i64,
+    pub new_quantity: i64,
+    pub previous_price: i64,
+    pub new_price: i64,
+}
+
+// Supplier catalog. A new price for the same supplier, ingredient and unit closes the
+// previous one, so past rows form the price history.
+#[table(
+    name = supplier_prices,
+    public,
+    index(name = supprice_supplier_idx, btree(columns = [supplier_id])),
+    index(name = supprice_ingredient_idx, btree(columns = [ingredient_id]))
+)]
+#[derive(Clone)]
+pub struct SupplierPrice {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub supplier_id: u64,
+    pub ingredient_id: u64,
+    // purchase unit the price is quoted in
+    pub unit_id: u64,
+    // per unit_id in cents
+    pub price: i64,
+    pub effective_from: Timestamp,
+    // exclusive; None while the price is current
+    pub effective_to: Option<Timestamp>,
+    pub created_by: Identity,
+    pub created_at: Timestamp,
+}
+
+#[table(
+    name = supplier_invoices,
+    public,
+    index(name = invoice_supplier_idx, btree(columns = [supplier_id])),
+    index(name = invoice_po_idx, btree(columns = [purchase_order_id]))
+)]
+#[derive(Clone)]
+pub struct SupplierInvoice {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub supplier_id: u64,
+    pub purchase_order_id: u64,
+    // the supplier's own number, unique per supplier
+    pub invoice_number: String,
+    pub invoice_date: Timestamp,
+    // invoice_date plus the supplier's payment terms
+    pub due_date: Timestamp,
+    // in cents, as stated on the invoice
+    pub total: i64,
+    // debit notes applied against the invoice, in cents
+    pub credited: i64,
+    pub amount_paid: i64,
+    pub match_status: InvoiceMatchStatus,
+    // why the three-way match failed, or the override note
+    pub match_notes: String,
+    pub payment_status: InvoicePaymentStatus,
+    pub recorded_by: Identity,
+    pub recorded_at: Timestamp,
+}
+
+// Quantities are in the ingredient's base unit; prices per ordered unit.
+#[table(name = supplier_invoice_lines, public, index(name = invline_invoice_idx, btree(columns = [invoice_id])))]
+#[derive(Clone)]
+pub struct SupplierInvoiceLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub invoice_id: u64,
+    pub po_item_id: u64,
+    pub ingredient_id: u64,
+    pub quantity: i64,
+    pub price: i64,
+    // PO price, price on the latest goods received note, and quantity received, less returns
+    // and not invoiced elsewhere, at the time of matching
+    pub po_price: i64,
+    pub received_price: Option<i64>,
+    pub received_quantity: i64,
+    pub matched: bool,
+}
+
+#[table(name = supplier_payments, public, index(name = suppay_invoice_idx, btree(columns = [invoice_id])))]
+#[derive(Clone)]
+pub struct SupplierPayment {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub invoice_id: u64,
+    pub supplier_id: u64,
+    // in cents
+    pub amount: i64,
+    pub method: PaymentMethod,
+    pub cash_flow_id: u64,
+    pub paid_by: Identity,
+    pub date: Timestamp,
+}
+
+// Accounts payable per supplier, kept in step with invoices and payments.
+#[table(name = supplier_payables, public)]
+#[derive(Clone)]
+pub struct SupplierPayable {
+    #[primary_key]
+    pub supplier_id: u64,
+    // unpaid invoice amounts in cents
+    pub outstanding: i64,
+    pub open_invoices: u32,
+}
+
+// Periodic supplier scorecard. Rates are in basis points (10_000 = 100%); None when the
+// period has no data for that measure.
+#[table(name = supplier_scores, public, index(name = supscore_supplier_idx, btree(columns = [supplier_id])))]
+#[derive(Clone)]
+pub struct SupplierScore {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub supplier_id: u64,
+    pub period_start: Timestamp,
+    pub period_end: Timestamp,
+    pub deliveries: u32,
+    // goods received notes dated on or before the PO's expected delivery
+    pub on_time_rate: Option<i64>,
+    // received vs ordered on POs closed in the period
+    pub fill_rate: Option<i64>,
+    // accepted vs delivered at goods receipt
+    pub quality_rate: Option<i64>,
+    // share of the accepted quantity later sent back on a purchase return
+    pub return_rate: Option<i64>,
+    // 100% minus the average catalog price change
+    pub price_stability: Option<i64>,
+    // weighted over the measures that have data
+    pub score: Option<i64>,
+    pub rating: i32,
+    pub computed_at: Timestamp,
+}
+
+#[table(name = supplier_score_schedule, scheduled(run_supplier_scoring))]
+pub struct SupplierScoreSchedule {
+    #[primary_key]
+    #[auto_inc]
+    pub scheduled_id: u64,
+    pub scheduled_at: ScheduleAt,
+}
+
+// Approval chain for purchase orders. A PO needs one approval per level, from the lowest
+// level up to the first level whose max_amount covers its total.
+#[table(name = po_approval_rules, public)]
+#[derive(Clone)]
+pub struct PoApprovalRule {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub level: u32,
+    pub role: UserRole,
+    // in cents; None means no upper limit
+    pub max_amount: Option<i64>,
+}
+
+#[table(name = po_approvals, public, index(name = poapproval_po_idx, btree(columns = [purchase_order_id])))]
+#[derive(Clone)]
+pub struct PoApproval {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub purchase_order_id: u64,
+    pub level: u32,
+    pub approver: Identity,
+    pub role: UserRole,
+    // PO total at the time of approval, in cents
+    pub total: i64,
+    pub approved_at: Timestamp,
+}
+
+// Goods sent back to the supplier after they were accepted on a goods received note.
+#[table(
+    name = purchase_returns,
+    public,
+    index(name = preturn_po_idx, btree(columns = [purchase_order_id])),
+    index(name = preturn_supplier_idx, btree(columns = [supplier_id]))
+)]
+#[derive(Clone)]
+pub struct PurchaseReturn {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub purchase_order_id: u64,
+    pub grn_id: u64,
+    pub supplier_id: u64,
+    pub outlet_id: u64,
+    // returned quantity × GRN price, in cents
+    pub total: i64,
+    pub resolution: PurchaseReturnResolution,
+    pub notes: String,
+    pub created_by: Identity,
+    pub date: Timestamp,
+    pub resolved_at: Option<Timestamp>,
+}
+
+// Quantities are in the ingredient's base unit; price per ordered unit.
+#[table(
+    name = purchase_return_lines,
+    public,
+    index(name = preturnline_return_idx, btree(columns = [return_id])),
+    index(name = preturnline_grnline_idx, btree(columns = [grn_line_id]))
+)]
+#[derive(Clone)]
+pub struct PurchaseReturnLine {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub return_id: u64,
+    pub grn_line_id: u64,
+    pub ingredient_id: u64,
+    pub quantity: i64,
+    pub price: i64,
+    pub movement_id: u64,
+    pub reason: String,
+}
+
+// Credit for the value of a return. It is applied to an invoice for the PO that still owes
+// at least its amount; until then it waits for the next invoice that bills the returned goods.
+#[table(name = supplier_debit_notes, public, index(name = debitnote_supplier_idx, btree(columns = [supplier_id])))]
+#[derive(Clone)]
+pub struct SupplierDebitNote {
+    #[primary_key]
+    #[auto_inc]
+    pub id: u64,
+    pub supplier_id: u64,
+    pub return_id: u64,
+    // in cents
+    pub amount: i64,
+    // invoice the credit was applied to, None while it is pending
+    pub invoice_id: Option<u64>,
+    // Pending while open; Replaced voids the note, Credited settles it
+    pub status: PurchaseReturnResolution,
+    pub date: Timestamp,
+}
+
+// Reducers
+
+#[reducer(init)]
+pub fn init(ctx: &ReducerContext) -> Result<(), String> {
+    // Time helpers
+    let now = ctx.timestamp;
+    let days = |d: i64| TimeDuration::from_duration(Duration::from_secs((d * 86400) as u64));
+    let hours = |h: i64| TimeDuration::from_duration(Duration::from_secs((h * 3600) as u64));
+    let safe_sub = |ts: Timestamp, dur: TimeDuration| ts.checked_sub(dur).unwrap_or(ts);
+    let safe_add = |ts: Timestamp, dur: TimeDuration| ts.checked_add(dur).unwrap_or(ts);
+
+    // 1) Outlets (18)
+    let outlet_names = [
+        "Kampoeng Steak Jakarta Pusat",
+        "Kampoeng Steak Jakarta Barat",
+        "Kampoeng Steak Tangerang",
+        "Kampoeng Steak Bekasi",
+        "Kampoeng Steak Bogor",
+        "Kampoeng Steak Depok",
+        "Kampoeng Steak Bandung",
+        "Kampoeng Steak Surabaya",
+        "Kampoeng Steak Semarang",
+        "Kampoeng Steak Yogyakarta",
+        "Kampoeng Steak Medan",
+        "Kampoeng Steak Palembang",
+        "Kampoeng Steak Makassar",
+        "Kampoeng Steak Bali",
+        "Kampoeng Steak Malang",
+        "Kampoeng Steak Solo",
+        "Kampoeng Steak Pekanbaru",
+        "Kampoeng Steak Balikpapan",
+    ];
+    for (i, name) in outlet_names.iter().enumerate() {
+        let area = name.split_whitespace().last().unwrap_or("Area").to_string();
+        let address = format!("Jl. Utama No. {}, {}", i + 1, area);
+        ctx.db.outlets().insert(Outlet {
+            id: 0,
+            name: name.to_string(),
+            area,
+            address,
+            status: OutletStatus::Open,
+        });
+    }
+
+    // Collect outlet ids
+    let outlet_ids: Vec<u64> = ctx.db.outlets().iter().map(|o| o.id).collect();
+    let outlet_count = outlet_ids.len();
+
+    // 2) Suppliers (5)
+    // (name, contact, payment terms, lead time) in days
+    let suppliers = [
+        ("PT Daging Prima", "021-5551234", 14, 1),
+        ("PT Sayur Fresh", "021-5555678", 7, 1),
+        ("PT Bumbu Nusantara", "021-7771111", 30, 3),
+        ("PT Minuman Sejuk", "021-8882222", 30, 2),
+        ("PT Kemasan Jaya", "021-9993333", 45, 5),
+    ];
+    for (name, contact, terms, lead_time) in suppliers.iter() {
+        ctx.db.suppliers().insert(Supplier {
+            id: 0,
+            name: (*name).to_string(),
+            contact: (*contact).to_string(),
+            rating: 0,
+            payment_terms_days: *terms,
+            lead_time_days: *lead_time,
+        });
+    }
+    let supplier_ids: Vec<u64> = ctx.db.suppliers().iter().map(|s| s.id).collect();
+
+    // 3) Products (10)
+    let products = [
+        ("Steak Wagyu", "Makanan", 185_000),
+        ("Steak Sirloin", "Makanan", 95_000),
+        ("Steak Tenderloin", "Makanan", 115_000),
+        ("Chicken Steak", "Makanan", 55_000),
+        ("Fish & Chips", "Makanan", 48_000),
+        ("Pasta Carbonara", "Makanan", 45_000),
+        ("French Fries", "Makanan", 20_000),
+        ("Orange Juice", "Minuman", 18_000),
+        ("Iced Tea", "Minuman", 10_000),
+        ("Coffee", "Minuman", 22_000),
+    ];
+    for (name, cat, price_rp) in products.iter() {
+        ctx.db.products().insert(Product {
+            id: 0,
+            name: (*name).to_string(),
+            category: (*cat).to_string(),
+            price: (*price_rp as i64) * 100,
+        });
+    }
+    let product_ids: Vec<u64> = ctx.db.products().iter().map(|p| p.id).collect();
+
+    // Full menu at every outlet, steaks priced 10% higher in Bali
+    for oid in outlet_ids.iter() {
+        for pid in product_ids.iter() {
+            ctx.db.outlet_menu_items().insert(OutletMenuItem {
+                id: 0,
+                outlet_id: *oid,
+                product_id: *pid,
+                enabled: true,
+                price_override: None,
+            });
+        }
+    }
+    for p in ctx.db.products().iter().filter(|p| p.name.starts_with("Steak")) {
+        ctx.db.area_menu_prices().insert(AreaMenuPrice {
+            id: 0,
+            area: "Bali".to_string(),
+            product_id: p.id,
+            price: p.price + p.price / 10,
+        });
+    }
+
+    // 3b) Units of measure
+    let units = [
+        ("g", "Gram", UnitDimension::Mass, 1),
+        ("kg", "Kilogram", UnitDimension::Mass, 1_000),
+        ("ml", "Mililiter", UnitDimension::Volume, 1),
+        ("L", "Liter", UnitDimension::Volume, 1_000),
+        ("pcs", "Pcs", UnitDimension::Count, 1),
+        ("box10kg", "Box 10 kg", UnitDimension::Mass, 10_000),
+        ("tray30", "Tray 30 butir", UnitDimension::Count, 30),
+    ];
+    for (code, name, dimension, factor) in units {
+        ctx.db.units_of_measure().insert(UnitOfMeasure {
+            id: 0,
+            code: code.to_string(),
+            name: name.to_string(),
+            dimension,
+            factor,
+        });
+    }
+    let unit_id = |code: &str| ctx.db.units_of_measure().code().find(code.to_string()).map(|u| u.id).unwrap_or(0);
+
+    // 4) Ingredients (20) distributed across outlets; seeded in kg / L / pcs, stored in base units
+    // (name, category, unit, cost in Rp per unit)
+    let ingredients = [
+        ("Daging Sapi", "Daging", "kg", 130_000),
+        ("Daging Ayam", "Daging", "kg", 38_000),
+        ("Ikan", "Daging", "kg", 60_000),
+        ("Kentang", "Sayur", "kg", 18_000),
+        ("Bawang", "Sayur", "kg", 30_000),
+        ("Tomat", "Sayur", "kg", 15_000),
+        ("Keju", "Dairy", "kg", 120_000),
+        ("Susu", "Dairy", "L", 20_000),
+        ("Telur", "Dairy", "pcs", 2_000),
+        ("Tepung", "Bahan Kering", "kg", 12_000),
+        ("Minyak", "Bahan Kering", "L", 18_000),
+        ("Garam", "Bumbu", "kg", 8_000),
+        ("Merica", "Bumbu", "kg", 150_000),
+        ("Saus", "Bumbu", "L", 35_000),
+        ("Pasta", "Bahan Kering", "kg", 30_000),
+        ("Kopi", "Minuman", "kg", 180_000),
+        ("Teh", "Minuman", "kg", 90_000),
+        ("Jus Jeruk", "Minuman", "L", 25_000),
+        ("Es Batu", "Minuman", "kg", 2_000),
+        ("Gula", "Bahan Kering", "kg", 16_000),
+    ];
+    for (i, (name, category, unit, cost_rp)) in ingredients.iter().enumerate() {
+        let (base, scale) = match *unit {
+            "kg" => ("g", 1_000),
+            "L" => ("ml", 1_000),
+            _ => ("pcs", 1),
+        };
+        let ing = ctx.db.ingredients().insert(Ingredient {
+            id: 0,
+            sku: format!("ING-{:03}", i + 1),
+            name: (*name).to_string(),
+            category: (*category).to_string(),
+            unit_id: unit_id(base),
+            halal: true,
+            status: IngredientStatus::Active,
+        });
+        // Stocked at every outlet
+        let unit_cost = (*cost_rp as i64) * 100 / scale;
+        for (k, oid) in outlet_ids.iter().enumerate() {
+            let min = (10 + ((i + k) as i64 % 10) * 5) * scale;
+            let cur = min + (20 + ((i + k) as i64 % 5) * 10) * scale;
+            let stock = ctx.db.ingredient_stock().insert(IngredientStock {
+                id: 0,
+                ingredient_id: ing.id,
+                outlet_id: *oid,
+                min_stock: min,
+                stock: 0,
+                avg_cost: 0,
+            });
+            post_stock_movement(ctx, stock.id, cur, StockMovementType::Adjustment, "init", "Opening stock", Some(unit_cost))?;
+        }
+    }
+    let ingredient_ids: Vec<u64> = ctx.db.ingredients().iter().map(|g| g.id).collect();
+
+    // 5) Employees (50)
+    let positions = ["Manager", "Koki", "Kasir", "Waiter"];
+    for i in 0..50 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let pos = positions[i as usize % positions.len()].to_string();
+        let salary = match pos.as_str() {
+            "Manager" => 6_000_000i64,
+            "Koki" => 4_000_000i64,
+            "Kasir" => 3_500_000i64,
+            _ => 3_000_000i64,
+        } * 100; // cents
+        ctx.db.employees().insert(Employee {
+            id: 0,
+            name: format!("Karyawan {}", i + 1),
+            position: pos,
+            outlet_id,
+            salary,
+            status: EmploymentStatus::Active,
+        });
+    }
+    let employee_ids: Vec<u64> = ctx.db.employees().iter().map(|e| e.id).collect();
+
+    // 6) Sales (30) with items
+    for i in 0..30 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let ts = safe_sub(now, days((i as i64 % 30) + 1)).checked_add(hours((i % 8) as i64)).unwrap_or(now);
+        let mut total = 0i64;
+        let sale = ctx.db.sales().insert(Sale {
+            id: 0,
+            outlet_id,
+            channel: if i % 5 == 0 { SalesChannel::Takeaway } else { SalesChannel::DineIn },
+            total: 0,
+            cogs: 0,
+            discount: 0,
+            payment_method: match i % 4 {
+                0 => PaymentMethod::Cash,
+                1 => PaymentMethod::Card,
+                2 => PaymentMethod::EWallet,
+                _ => PaymentMethod::Transfer,
+            },
+            date: ts,
+        });
+        let item_count: usize = 1 + (i as usize % 3);
+        for j in 0..item_count {
+            let prod_id = product_ids[(i as usize + j) % product_ids.len()];
+            if let Some(prod) = ctx.db.products().id().find(prod_id) {
+                let qty: i32 = 1 + (j as i32);
+                total = total.saturating_add(prod.price.saturating_mul(qty as i64));
+                ctx.db.sale_items().insert(SaleItem {
+                    id: 0,
+                    sale_id: sale.id,
+                    product_id: prod.id,
+                    quantity: qty,
+                    price: prod.price,
+                });
+            }
+        }
+        if let Some(mut s) = ctx.db.sales().id().find(sale.id) {
+            s.total = total;
+            ctx.db.sales().id().update(s);
+        }
+    }
+
+    // 7) Purchase Orders (15) with items
+    for i in 0..15 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let supplier_id = supplier_ids[i as usize % supplier_ids.len()];
+        let status = match i % 4 {
+            0 => POStatus::Created,
+            1 => POStatus::Ordered,
+            2 => POStatus::Received,
+            _ => POStatus::Cancelled,
+        };
+        let date = safe_sub(now, days(40 - (i as i64 % 30)));
+        let mut po = ctx.db.purchase_orders().insert(PurchaseOrder {
+            id: 0,
+            outlet_id,
+            supplier_id,
+            total: 0,
+            status: status.clone(),
+            date,
+            expected_delivery: expected_delivery(ctx, supplier_id, date),
+            created_by: ctx.identity(),
+            revision: 0,
+        });
+        // Add 1-3 items from ingredients
+        let item_count = 1 + (i % 3);
+        for j in 0..item_count {
+            let ing_id = ingredient_ids[(i as usize + j as usize) % ingredient_ids.len()];
+            let Some(ing) = ctx.db.ingredients().id().find(ing_id) else {
+                continue;
+            };
+            // ordered per kg / L, or per piece
+            let purchase_unit = match ctx.db.units_of_measure().id().find(ing.unit_id).map(|u| u.dimension) {
+                Some(UnitDimension::Mass) => "kg",
+                Some(UnitDimension::Volume) => "L",
+                _ => "pcs",
+            };
+            let unit = ctx.db.units_of_measure().code().find(purchase_unit.to_string());
+            let qty = 5 + (j as i64) * 3;
+            let price = 25_000i64 * 100 + (j as i64) * 5_000 * 100;
+            ctx.db.purchase_order_items().insert(PurchaseOrderItem {
+                id: 0,
+                purchase_order_id: po.id,
+                ingredient_id: ing_id,
+                unit_id: unit.as_ref().map(|u| u.id).unwrap_or(ing.unit_id),
+                ordered_quantity: qty,
+                quantity: qty * unit.as_ref().map(|u| u.factor).unwrap_or(1),
+                received_quantity: if status == POStatus::Received {
+                    qty * unit.as_ref().map(|u| u.factor).unwrap_or(1)
+                } else {
+                    0
+                },
+                price,
+                catalog_price: None,
+                price_flagged: false,
+            });
+        }
+        po.total = ctx.db.purchase_order_items().poi_po_idx().filter(po.id).map(|it| po_line_total(&it)).sum();
+        ctx.db.purchase_orders().id().update(po);
+    }
+
+    // 8) Distributions (10)
+    for i in 0..10 {
+        let from_outlet_id = outlet_ids[i as usize % outlet_count];
+        let to_outlet_id = outlet_ids[(i as usize + 1) % outlet_count];
+        let ingredient_id = ingredient_ids[i as usize % ingredient_ids.len()];
+        // 5-14 kg / L, or pieces, in base units
+        let base_unit = ctx.db.ingredients().id().find(ingredient_id).map(|g| g.unit_id);
+        let scale = match base_unit.and_then(|u| ctx.db.units_of_measure().id().find(u)).map(|u| u.dimension) {
+            Some(UnitDimension::Mass) | Some(UnitDimension::Volume) => 1_000,
+            _ => 1,
+        };
+        let quantity = (5 + (i as i64 % 10)) * scale;
+        let status = match i % 3 {
+            0 => DistributionStatus::Pending,
+            1 => DistributionStatus::InTransit,
+            _ => DistributionStatus::Delivered,
+        };
+        ctx.db.distributions().insert(Distribution {
+            id: 0,
+            from_outlet_id,
+            to_outlet_id,
+            ingredient_id,
+            quantity,
+            status,
+            date: safe_sub(now, days(20 - (i as i64 % 15))),
+        });
+    }
+
+    // 9) Daily Checklists (18) one per outlet
+    for (i, oid) in outlet_ids.iter().enumerate() {
+        ctx.db.daily_checklists().insert(DailyChecklist {
+            id: 0,
+            outlet_id: *oid,
+            checklist_name: "Buka toko, cek kebersihan, stok bahan, mesin kasir".to_string(),
+            is_completed: (i % 2 == 0),
+            date: safe_sub(now, days(i as i64 % 10)),
+        });
+    }
+
+    // 10) Shift Reports (10)
+    for i in 0..10 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let employee_id = employee_ids[i as usize % employee_ids.len()];
+        let shift_start = safe_sub(now, days(i as i64 % 20));
+        let shift_end = safe_add(shift_start, hours(8));
+        let initial_cash = (1_000_000i64 + (i as i64) * 100_000) * 100;
+        let final_cash = initial_cash.saturating_add(500_000i64 * 100);
+        let status = if i % 3 == 0 { ShiftStatus::Open } else { ShiftStatus::Closed };
+        ctx.db.shift_reports().insert(ShiftReport {
+            id: 0,
+            outlet_id,
+            employee_id,
+            shift_start,
+            shift_end,
+            initial_cash,
+            final_cash: if status == ShiftStatus::Open { 0 } else { final_cash },
+            status,
+        });
+    }
+
+    // 11) Candidates (15)
+    for i in 0..15 {
+        let st = match i % 4 {
+            0 => CandidateStatus::Applied,
+            1 => CandidateStatus::Interview,
+            2 => CandidateStatus::Hired,
+            _ => CandidateStatus::Rejected,
+        };
+        ctx.db.candidates().insert(Candidate {
+            id: 0,
+            name: format!("Pelamar {}", i + 1),
+            position: positions[i as usize % positions.len()].to_string(),
+            phone: format!("08{:09}", 111111111 + i),
+            email: format!("pelamar{}@mail.com", i + 1),
+            status: st,
+        });
+    }
+
+    // 12) Promotions (5)
+    for i in 0..5 {
+        let (start_date, end_date) = if i == 0 {
+            (safe_sub(now, days(5)), safe_add(now, days(5)))
+        } else if i == 1 {
+            (safe_add(now, days(5)), safe_add(now, days(15)))
+        } else {
+            let start = safe_sub(now, days(20 + (i as i64 * 3)));
+            let end = safe_sub(now, days(10 + (i as i64 * 2)));
+            (start, end)
+        };
+        ctx.db.promotions().insert(Promotion {
+            id: 0,
+            name: format!("Promo Spesial {}", i + 1),
+            discount_type: if i % 2 == 0 { DiscountType::Percentage } else { DiscountType::FixedAmount },
+            discount_value: if i % 2 == 0 { 1000 } else { 10_000 * 100 }, // 10% or Rp10.000
+            start_date,
+            end_date,
+            status: if i == 0 { PromotionStatus::Active } else { PromotionStatus::Ended },
+        });
+    }
+
+    // 13) Assets (30)
+    let asset_names = ["Kompor", "Kulkas", "AC", "Meja", "Kursi", "Wastafel"];
+    let asset_cats = ["Dapur", "Pendingin", "HVAC", "Furnitur", "Furnitur", "Sanitasi"];
+    for i in 0..30 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let idx = i as usize % asset_names.len();
+        let last_maint = safe_sub(now, days(15 - (i as i64 % 10)));
+        let status = match i % 5 {
+            1 => AssetStatus::Maintenance,
+            2 => AssetStatus::Broken,
+            _ => AssetStatus::InUse,
+        };
+        ctx.db.assets().insert(Asset {
+            id: 0,
+            outlet_id,
+            name: asset_names[idx].to_string(),
+            category: asset_cats[idx].to_string(),
+            status,
+            last_maintenance: last_maint,
+        });
+    }
+
+    // Chillers hold 0–5 °C, checked every 4 hours
+    let chillers: Vec<u64> = ctx.db.assets().iter().filter(|a| a.name == "Kulkas").map(|a| a.id).collect();
+    for asset_id in chillers {
+        set_temperature_range(ctx, asset_id, 0, 50, 4 * 60, 2)?;
+    }
+
+    // 14) CashFlow (50)
+    let categories = ["Sales", "Purchase", "Salary", "Rent", "Utilities"];
+    for i in 0..50 {
+        let outlet_id = outlet_ids[i as usize % outlet_count];
+        let ctype = if i % 2 == 0 { CashFlowType::Inflow } else { CashFlowType::Outflow };
+        let amount = (200_000i64 + ((i % 10) as i64) * 50_000) * 100;
+        ctx.db.cash_flow().insert(CashFlow {
+            id: 0,
+            outlet_id,
+            ctype,
+            category: categories[i as usize % categories.len()].to_string(),
+            amount,
+            date: safe_sub(now, days(i as i64 % 60)),
+            description: "Catatan kas harian".to_string(),
+        });
+    }
+
+    // 15) Users (seed 1 Admin - no conditional checks)
+    ctx.db.users().insert(User {
+        id: ctx.identity(),
+        username: "admin_pusat".to_string(),
+        role: UserRole::AdminPusat,
+        outlet_id: None,
+    });
+
+    // 16) Dining tables (6 per outlet)
+    let table_areas = ["Non-Smoking", "Non-Smoking", "Non-Smoking", "Smoking", "Smoking", "Outdoor"];
+    for oid in outlet_ids.iter() {
+        for (n, area) in table_areas.iter().enumerate() {
+            ctx.db.dining_tables().insert(DiningTable {
+                id: 0,
+                outlet_id: *oid,
+                number: format!("T{:02}", n + 1),
+                capacity: if n % 3 == 2 { 6 } else { 4 },
+                area: (*area).to_string(),
+                status: TableStatus::Available,
+            });
+        }
+    }
+
+    // 17) Delivery platform mappings at the first outlet, so local test payloads can be ingested
+    let platforms = [
+        (DeliveryPlatform::GoFood, "GF"),
+        (DeliveryPlatform::GrabFood, "GB"),
+        (DeliveryPlatform::ShopeeFood, "SF"),
+    ];
+    for p in ctx.db.products().iter() {
+        for (platform, prefix) in platforms.clone() {
+            ctx.db.platform_product_mappings().insert(PlatformProductMapping {
+                id: 0,
+                platform,
+                outlet_id: outlet_ids[0],
+                external_item_id: format!("{}-{}", prefix, p.id),
+                product_id: p.id,
+            });
+        }
+    }
+
+    // 18) Recipes and initial availability
+    // quantities in base units (g / ml / pcs) per portion
+    let recipe_lines: [(&str, &[(&str, i64)]); 10] = [
+        ("Steak Wagyu", &[("Daging Sapi", 200), ("Kentang", 150), ("Saus", 50)]),
+        ("Steak Sirloin", &[("Daging Sapi", 250), ("Kentang", 150), ("Saus", 50)]),
+        ("Steak Tenderloin", &[("Daging Sapi", 200), ("Kentang", 150)]),
+        ("Chicken Steak", &[("Daging Ayam", 200), ("Kentang", 150)]),
+        ("Fish & Chips", &[("Ikan", 180), ("Kentang", 150), ("Tepung", 50)]),
+        ("Pasta Carbonara", &[("Pasta", 120), ("Keju", 30), ("Susu", 100), ("Telur", 2)]),
+        ("French Fries", &[("Kentang", 200), ("Minyak", 30)]),
+        ("Orange Juice", &[("Jus Jeruk", 250)]),
+        ("Iced Tea", &[("Teh", 5), ("Gula", 20), ("Es Batu", 150)]),
+        ("Coffee", &[("Kopi", 18), ("Gula", 10)]),
+    ];
+    for (product_name, lines) in recipe_lines.iter() {
+        let Some(prod) = ctx.db.products().iter().find(|p| p.name == *product_name) else {
+            continue;
+        };
+        for (ingredient_name, qty) in lines.iter() {
+            if let Some(ing) = ctx.db.ingredients().iter().find(|g| g.name == *ingredient_name) {
+                ctx.db.recipes().insert(Recipe {
+                    id: 0,
+                    product_id: prod.id,
+                    ingredient_id: ing.id,
+                    quantity: *qty,
+                });
+            }
+        }
+    }
+    for m in ctx.db.outlet_menu_items().iter() {
+        refresh_product_availability(ctx, m.outlet_id, m.product_id);
+    }
+
+    // PO approvals: outlet manager up to Rp5 juta, area manager up to Rp25 juta, then head office
+    let approval_rules = [
+        (1, UserRole::OutletManager, Some(5_000_000i64 * 100)),
+        (2, UserRole::AreaManager, Some(25_000_000i64 * 100)),
+        (3, UserRole::AdminPusat, None),
+        (3, UserRole::Finance, None),
     ];
-    for (i, (name, unit)) in ingredients.iter().enumerate() {
-        let outlet_id = outlet_ids[i % outlet_count];
-        let min = 10 + (i as i64 % 10) * 5;
-        let cur = min + 20 + (i as i64 % 5) * 10;
-        ctx.db.ingredients().insert(Ingredient {
+    for (level, role, max_amount) in approval_rules {
+        ctx.db.po_approval_rules().insert(PoApprovalRule {
+            id: 0,
+            level,
+            role,
+            max_amount,
+        });
+    }
+
+    // 19) Reorder rules: par at twice min_stock, ordered per kg / L / pcs
+    for st in ctx.db.ingredient_stock().iter() {
+        let Some(ing) = ctx.db.ingredients().id().find(st.ingredient_id) else {
+            continue;
+        };
+        let supplier_idx = match ing.name.as_str() {
+            "Daging Sapi" | "Daging Ayam" | "Ikan" => 0,
+            "Kentang" | "Bawang" | "Tomat" => 1,
+            "Kopi" | "Teh" | "Jus Jeruk" | "Es Batu" => 3,
+            _ => 2,
+        };
+        let purchase_unit = match unit_code(ctx, ing.unit_id).as_str() {
+            "g" => "kg",
+            "ml" => "L",
+            _ => "pcs",
+        };
+        ctx.db.reorder_rules().insert(ReorderRule {
+            id: 0,
+            ingredient_id: ing.id,
+            outlet_id: st.outlet_id,
+            par_level: st.min_stock * 2,
+            reorder_qty: 0,
+            purchase_unit_id: unit_id(purchase_unit),
+            preferred_supplier_id: supplier_ids[supplier_idx % supplier_ids.len()],
+        });
+    }
+
+    // 20) Semi-finished items made in the back of house
+    let portion = ctx.db.ingredients().insert(Ingredient {
+        id: 0,
+        sku: format!("ING-{:03}", ingredient_ids.len() + 1),
+        name: "Porsi Sirloin 250g".to_string(),
+        category: "Setengah Jadi".to_string(),
+        unit_id: unit_id("pcs"),
+        halal: true,
+        status: IngredientStatus::Active,
+    });
+    if let Some(beef) = ctx.db.ingredients().iter().find(|g| g.name == "Daging Sapi") {
+        create_production_order(
+            ctx,
+            outlet_ids[0],
+            portion.id,
+            unit_id("pcs"),
+            36,
+            vec![ProductionInputLine { ingredient_id: beef.id, unit_id: unit_id("kg"), quantity: 10 }],
+        )?;
+    }
+
+    // 21) Supplier price lists: last quarter's price and the current one, per kg / L / pcs
+    for rule in ctx.db.reorder_rules().iter().filter(|r| r.outlet_id == outlet_ids[0]) {
+        let Some(st) = outlet_stock(ctx, rule.ingredient_id, rule.outlet_id) else {
+            continue;
+        };
+        let factor = ctx.db.units_of_measure().id().find(rule.purchase_unit_id).map(|u| u.factor).unwrap_or(1);
+        let current = st.avg_cost.saturating_mul(factor);
+        for (from, price) in [(days(90), current * 95 / 100), (days(30), current)] {
+            set_supplier_price(
+                ctx,
+                rule.preferred_supplier_id,
+                rule.ingredient_id,
+                rule.purchase_unit_id,
+                price,
+                safe_sub(now, from),
+            )?;
+        }
+    }
+
+    // 22) Initial supplier scorecards
+    score_all_suppliers(ctx);
+
+    // Background jobs
+    ctx.db.reservation_release_schedule().insert(ReservationReleaseSchedule {
+        scheduled_id: 0,
+        scheduled_at: Duration::from_secs(5 * 60).into(),
+    });
+    ctx.db.lot_expiry_schedule().insert(LotExpirySchedule {
+        scheduled_id: 0,
+        scheduled_at: Duration::from_secs(60 * 60).into(),
+    });
+    ctx.db.temperature_check_schedule().insert(TemperatureCheckSchedule {
+        scheduled_id: 0,
+        scheduled_at: Duration::from_secs(15 * 60).into(),
+    });
+    ctx.db.supplier_score_schedule().insert(SupplierScoreSchedule {
+        scheduled_id: 0,
+        scheduled_at: Duration::from_secs(7 * 24 * 60 * 60).into(),
+    });
+    ctx.db.reorder_schedule().insert(ReorderSchedule {
+        scheduled_id: 0,
+        scheduled_at: Duration::from_secs(24 * 60 * 60).into(),
+    });
+
+    Ok(())
+}
+
+// Outlet management
+
+#[reducer]
+pub fn create_outlet(
+    ctx: &ReducerContext,
+    name: String,
+    area: String,
+    address: String,
+) -> Result<(), String> {
+    ctx.db.outlets().insert(Outlet {
+        id: 0,
+        name,
+        area,
+        address,
+        status: OutletStatus::Open,
+    });
+    Ok(())
+}
+
+#[reducer]
+pub fn update_outlet(
+    ctx: &ReducerContext,
+    id: u64,
+    name: String,
+    area: String,
+    address: String,
+    status: OutletStatus,
+) -> Result<(), String> {
+    if let Some(mut o) = ctx.db.outlets().id().find(id) {
+        o.name = name;
+        o.area = area;
+        o.address = address;
+        o.status = status;
+        ctx.db.outlets().id().update(o);
+        Ok(())
+    } else {
+        Err("Outlet not found".into())
+    }
+}
+
+#[reducer]
+pub fn delete_outlet(ctx: &ReducerContext, id: u64) -> Result<(), String> {
+    ctx.db.outlets().id().delete(id);
+    Ok(())
+}
+
+// Employee management
+
+#[reducer]
+pub fn create_employee(
+    ctx: &ReducerContext,
+    name: String,
+    position: String,
+    outlet_id: u64,
+    salary: i64,
+    status: EmploymentStatus,
+) -> Result<(), String> {
+    ctx.db.employees().insert(Employee {
+        id: 0,
+        name,
+        position,
+        outlet_id,
+        salary,
+        status,
+    });
+    Ok(())
+}
+
+#[reducer]
+pub fn update_employee_status(
+    ctx: &ReducerContext,
+    employee_id: u64,
+    status: EmploymentStatus,
+) -> Result<(), String> {
+    if let Some(mut e) = ctx.db.employees().id().find(employee_id) {
+        e.status = status.clone();
+        ctx.db.employees().id().update(e);
+        Ok(())
+    } else {
+        Err("Employee not found".into())
+    }
+}
+
+// Menu catalog
+
+fn find_menu_item(ctx: &ReducerContext, outlet_id: u64, product_id: u64) -> Option<OutletMenuItem> {
+    ctx.db.outlet_menu_items().menu_outlet_idx().filter(outlet_id).find(|m| m.product_id == product_id)
+}
+
+// Outlet override, then area price, then the catalog base price.
+// Returns None when the product is not enabled at the outlet.
+fn effective_price(ctx: &ReducerContext, outlet_id: u64, product_id: u64) -> Option<i64> {
+    let item = find_menu_item(ctx, outlet_id, product_id).filter(|m| m.enabled)?;
+    if let Some(price) = item.price_override {
+        return Some(price);
+    }
+    let product = ctx.db.products().id().find(product_id)?;
+    let area = ctx.db.outlets().id().find(outlet_id).map(|o| o.area).unwrap_or_default();
+    let area_price = ctx
+        .db
+        .area_menu_prices()
+        .areaprice_product_idx()
+        .filter(product_id)
+        .find(|a| a.area == area)
+        .map(|a| a.price);
+    Some(area_price.unwrap_or(product.price))
+}
+
+#[reducer]
+pub fn add_product(
+    ctx: &ReducerContext,
+    name: String,
+    category: String,
+    price: i64,
+) -> Result<(), String> {
+    if price < 0 {
+        return Err("Price must not be negative".into());
+    }
+    ctx.db.products().insert(Product {
+        id: 0,
+        name,
+        category,
+        price,
+    });
+    Ok(())
+}
+
+#[reducer]
+pub fn update_product(
+    ctx: &ReducerContext,
+    product_id: u64,
+    name: String,
+    category: String,
+    price: i64,
+) -> Result<(), String> {
+    if price < 0 {
+        return Err("Price must not be negative".into());
+    }
+    if let Some(mut p) = ctx.db.products().id().find(product_id) {
+        p.name = name;
+        p.category = category;
+        p.price = price;
+        ctx.db.products().id().update(p);
+        Ok(())
+    } else {
+        Err("Product not found".into())
+    }
+}
+
+#[reducer]
+pub fn set_outlet_menu_item(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    product_id: u64,
+    enabled: bool,
+    price_override: Option<i64>,
+) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    if price_override.is_some_and(|p| p < 0) {
+        return Err("Price must not be negative".into());
+    }
+    if let Some(mut m) = find_menu_item(ctx, outlet_id, product_id) {
+        m.enabled = enabled;
+        m.price_override = price_override;
+        ctx.db.outlet_menu_items().id().update(m);
+    } else {
+        ctx.db.outlet_menu_items().insert(OutletMenuItem {
+            id: 0,
+            outlet_id,
+            product_id,
+            enabled,
+            price_override,
+        });
+    }
+    if enabled {
+        refresh_product_availability(ctx, outlet_id, product_id);
+    }
+    Ok(())
+}
+
+// Enables a catalog item at every outlet, or only at outlets in the given area.
+#[reducer]
+pub fn roll_out_product(ctx: &ReducerContext, product_id: u64, area: Option<String>) -> Result<(), String> {
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    let outlet_ids: Vec<u64> = ctx
+        .db
+        .outlets()
+        .iter()
+        .filter(|o| area.as_ref().is_none_or(|a| &o.area == a))
+        .map(|o| o.id)
+        .collect();
+    for outlet_id in outlet_ids {
+        if let Some(mut m) = find_menu_item(ctx, outlet_id, product_id) {
+            if m.enabled {
+                continue;
+            }
+            m.enabled = true;
+            ctx.db.outlet_menu_items().id().update(m);
+        } else {
+            ctx.db.outlet_menu_items().insert(OutletMenuItem {
+                id: 0,
+                outlet_id,
+                product_id,
+                enabled: true,
+                price_override: None,
+            });
+        }
+        refresh_product_availability(ctx, outlet_id, product_id);
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn set_area_price(
+    ctx: &ReducerContext,
+    area: String,
+    product_id: u64,
+    price: Option<i64>,
+) -> Result<(), String> {
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    let existing = ctx
+        .db
+        .area_menu_prices()
+        .areaprice_product_idx()
+        .filter(product_id)
+        .find(|a| a.area == area);
+    match (existing, price) {
+        (_, Some(p)) if p < 0 => return Err("Price must not be negative".into()),
+        (Some(mut a), Some(p)) => {
+            a.price = p;
+            ctx.db.area_menu_prices().id().update(a);
+        }
+        (Some(a), None) => {
+            ctx.db.area_menu_prices().id().delete(a.id);
+        }
+        (None, Some(p)) => {
+            ctx.db.area_menu_prices().insert(AreaMenuPrice {
+                id: 0,
+                area,
+                product_id,
+                price: p,
+            });
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+// Units of measure
+
+fn unit_code(ctx: &ReducerContext, unit_id: u64) -> String {
+    ctx.db.units_of_measure().id().find(unit_id).map(|u| u.code).unwrap_or_default()
+}
+
+// Converts a quantity in `unit_id` to the ingredient's base unit.
+fn to_base_quantity(ctx: &ReducerContext, ingredient: &Ingredient, unit_id: u64, quantity: i64) -> Result<i64, String> {
+    let unit = ctx.db.units_of_measure().id().find(unit_id).ok_or("Unit not found")?;
+    let base = ctx.db.units_of_measure().id().find(ingredient.unit_id).ok_or("Unit not found")?;
+    if unit.dimension != base.dimension {
+        return Err(format!("{} cannot be measured in {}", ingredient.name, unit.code));
+    }
+    quantity.checked_mul(unit.factor).ok_or_else(|| "Quantity overflow".into())
+}
+
+#[reducer]
+pub fn add_unit_of_measure(
+    ctx: &ReducerContext,
+    code: String,
+    name: String,
+    dimension: UnitDimension,
+    factor: i64,
+) -> Result<(), String> {
+    if factor <= 0 {
+        return Err("Factor must be positive".into());
+    }
+    if ctx.db.units_of_measure().code().find(&code).is_some() {
+        return Err(format!("Unit {} already exists", code));
+    }
+    ctx.db.units_of_measure().insert(UnitOfMeasure {
+        id: 0,
+        code,
+        name,
+        dimension,
+        factor,
+    });
+    Ok(())
+}
+
+// Inventory
+
+fn add_inventory_value(ctx: &ReducerContext, outlet_id: u64, change: i64) {
+    if let Some(mut v) = ctx.db.outlet_inventory_values().outlet_id().find(outlet_id) {
+        v.value = v.value.saturating_add(change);
+        v.updated_at = ctx.timestamp;
+        ctx.db.outlet_inventory_values().outlet_id().update(v);
+    } else {
+        ctx.db.outlet_inventory_values().insert(OutletInventoryValue {
+            outlet_id,
+            value: change,
+            updated_at: ctx.timestamp,
+        });
+    }
+}
+
+fn outlet_stock(ctx: &ReducerContext, ingredient_id: u64, outlet_id: u64) -> Option<IngredientStock> {
+    ctx.db.ingredient_stock().stock_ingredient_outlet_idx().filter((ingredient_id, outlet_id)).next()
+}
+
+// Stock record for the ingredient at the outlet, created empty if the outlet has never stocked it.
+fn ensure_outlet_stock(ctx: &ReducerContext, ingredient_id: u64, outlet_id: u64) -> Result<IngredientStock, String> {
+    if let Some(st) = outlet_stock(ctx, ingredient_id, outlet_id) {
+        return Ok(st);
+    }
+    if ctx.db.ingredients().id().find(ingredient_id).is_none() {
+        return Err("Ingredient not found".into());
+    }
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    let st = ctx.db.ingredient_stock().insert(IngredientStock {
+        id: 0,
+        ingredient_id,
+        outlet_id,
+        min_stock: 0,
+        stock: 0,
+        avg_cost: 0,
+    });
+    // Every stock row gets an alert check when it appears, not only after its first movement.
+    check_stock_alert(ctx, st.id);
+    Ok(st)
+}
+
+fn require_outlet_stock(ctx: &ReducerContext, ingredient_id: u64, outlet_id: u64) -> Result<IngredientStock, String> {
+    outlet_stock(ctx, ingredient_id, outlet_id)
+        .ok_or_else(|| format!("Ingredient {} is not stocked at outlet {}", ingredient_id, outlet_id))
+}
+
+// The only place IngredientStock.stock is changed. Rejects movements that would take stock below zero.
+fn post_stock_movement(
+    ctx: &ReducerContext,
+    stock_id: u64,
+    delta: i64,
+    movement_type: StockMovementType,
+    reference: &str,
+    reason: &str,
+    inbound_cost: Option<i64>,
+) -> Result<StockMovement, String> {
+    let mut st = ctx.db.ingredient_stock().id().find(stock_id).ok_or("Stock record not found")?;
+    let balance = st.stock.checked_add(delta).ok_or("Stock overflow")?;
+    if balance < 0 {
+        let ing = ctx.db.ingredients().id().find(st.ingredient_id).ok_or("Ingredient not found")?;
+        let unit = unit_code(ctx, ing.unit_id);
+        return Err(format!("Insufficient stock of {}: have {} {}, need {} {}", ing.name, st.stock, unit, -delta, unit));
+    }
+    // Inbound movements with a known cost move the average; everything else is valued at it.
+    let unit_cost = match inbound_cost {
+        Some(cost) if delta > 0 => cost,
+        _ => st.avg_cost,
+    };
+    let value_before = st.stock.saturating_mul(st.avg_cost);
+    if delta > 0 && balance > 0 {
+        st.avg_cost = value_before.saturating_add(delta.saturating_mul(unit_cost)) / balance;
+    }
+    let value_after = balance.saturating_mul(st.avg_cost);
+    st.stock = balance;
+    let (ingredient_id, outlet_id) = (st.ingredient_id, st.outlet_id);
+    let count_session_id = active_count_session(ctx, outlet_id).map(|s| s.id);
+    if count_session_id.is_some() && movement_type == StockMovementType::Adjustment {
+        return Err("Manual adjustments are blocked while a stock count is in progress".into());
+    }
+    ctx.db.ingredient_stock().id().update(st);
+    let movement = ctx.db.stock_movements().insert(StockMovement {
+        id: 0,
+        ingredient_id,
+        outlet_id,
+        delta,
+        balance_after: balance,
+        unit_cost,
+        value: delta.saturating_mul(unit_cost),
+        movement_type,
+        reference: reference.to_string(),
+        reason: reason.to_string(),
+        count_session_id,
+        created_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    // Returns draw from the lot they are returning themselves.
+    if delta < 0 && movement.movement_type != StockMovementType::PurchaseReturn {
+        consume_lots_fefo(ctx, &movement, -delta)?;
+    }
+    add_inventory_value(ctx, outlet_id, value_after - value_before);
+    refresh_availability_for_ingredient(ctx, ingredient_id, outlet_id);
+    check_stock_alert(ctx, stock_id);
+    Ok(movement)
+}
+
+#[reducer]
+pub fn add_ingredient(
+    ctx: &ReducerContext,
+    sku: String,
+    name: String,
+    category: String,
+    unit_id: u64,
+    halal: bool,
+) -> Result<(), String> {
+    let unit = ctx.db.units_of_measure().id().find(unit_id).ok_or("Unit not found")?;
+    if unit.factor != 1 {
+        return Err(format!("Ingredients must be stocked in a base unit, not {}", unit.code));
+    }
+    if ctx.db.ingredients().sku().find(&sku).is_some() {
+        return Err(format!("SKU {} already exists", sku));
+    }
+    // Outlets start stocking it through update_min_stock or receive_stock.
+    ctx.db.ingredients().insert(Ingredient {
+        id: 0,
+        sku,
+        name,
+        category,
+        unit_id,
+        halal,
+        status: IngredientStatus::Active,
+    });
+    Ok(())
+}
+
+#[reducer]
+pub fn update_ingredient_status(
+    ctx: &ReducerContext,
+    ingredient_id: u64,
+    status: IngredientStatus,
+) -> Result<(), String> {
+    let mut ing = ctx.db.ingredients().id().find(ingredient_id).ok_or("Ingredient not found")?;
+    ing.status = status;
+    ctx.db.ingredients().id().update(ing);
+    let outlet_ids: Vec<u64> =
+        ctx.db.ingredient_stock().stock_ingredient_outlet_idx().filter(ingredient_id).map(|st| st.outlet_id).collect();
+    for outlet_id in outlet_ids {
+        refresh_availability_for_ingredient(ctx, ingredient_id, outlet_id);
+    }
+    Ok(())
+}
+
+// Also starts stocking the ingredient at the outlet if it was not stocked there yet.
+#[reducer]
+pub fn update_min_stock(
+    ctx: &ReducerContext,
+    ingredient_id: u64,
+    outlet_id: u64,
+    min_stock: i64,
+) -> Result<(), String> {
+    if min_stock < 0 {
+        return Err("Min stock must not be negative".into());
+    }
+    let mut st = ensure_outlet_stock(ctx, ingredient_id, outlet_id)?;
+    st.min_stock = min_stock;
+    let stock_id = st.id;
+    ctx.db.ingredient_stock().id().update(st);
+    refresh_availability_for_ingredient(ctx, ingredient_id, outlet_id);
+    check_stock_alert(ctx, stock_id);
+    Ok(())
+}
+
+#[reducer]
+pub fn adjust_inventory(
+    ctx: &ReducerContext,
+    ingredient_id: u64,
+    outlet_id: u64,
+    delta: i64,
+    reason: String,
+) -> Result<(), String> {
+    if delta == 0 {
+        return Err("Adjustment must change the stock".into());
+    }
+    if reason.trim().is_empty() {
+        return Err("Adjustments require a reason".into());
+    }
+    let st = ensure_outlet_stock(ctx, ingredient_id, outlet_id)?;
+    post_stock_movement(ctx, st.id, delta, StockMovementType::Adjustment, "", &reason, None)?;
+    Ok(())
+}
+
+// Lots
+
+// Lots expiring within this many days are flagged NearExpiry.
+const NEAR_EXPIRY_DAYS: u64 = 3;
+
+fn create_lot(
+    ctx: &ReducerContext,
+    movement: &StockMovement,
+    lot_number: String,
+    supplier_id: Option<u64>,
+    source_lot_id: Option<u64>,
+    expires_at: Option<Timestamp>,
+) -> StockLot {
+    let lot = ctx.db.stock_lots().insert(StockLot {
+        id: 0,
+        ingredient_id: movement.ingredient_id,
+        outlet_id: movement.outlet_id,
+        lot_number,
+        supplier_id,
+        source_lot_id,
+        quantity_received: movement.delta,
+        quantity_remaining: movement.delta,
+        unit_cost: movement.unit_cost,
+        received_at: movement.date,
+        expires_at,
+        status: LotStatus::Active,
+    });
+    ctx.db.lot_movements().insert(LotMovement {
+        id: 0,
+        lot_id: lot.id,
+        movement_id: movement.id,
+        quantity: movement.delta,
+    });
+    lot
+}
+
+// Draws `quantity` from the ingredient's lots, first expiry first out. Lots without an
+// expiry go last. Anything beyond the tracked lots comes from untracked stock.
+fn lot_is_expired(ctx: &ReducerContext, lot: &StockLot) -> bool {
+    lot.status == LotStatus::Expired || lot.expires_at.is_some_and(|at| at < ctx.timestamp)
+}
+
+// Draws the movement's quantity from the outlet's lots, earliest expiry first. Only waste,
+// adjustments, counts and returns may take expired lots; anything else that would have to
+// be covered by expired stock is refused so it gets written off instead of used.
+fn consume_lots_fefo(ctx: &ReducerContext, movement: &StockMovement, quantity: i64) -> Result<(), String> {
+    let allow_expired = matches!(
+        movement.movement_type,
+        StockMovementType::Waste
+            | StockMovementType::Adjustment
+            | StockMovementType::Count
+            | StockMovementType::PurchaseReturn
+    );
+    let (usable, expired): (Vec<StockLot>, Vec<StockLot>) = ctx
+        .db
+        .stock_lots()
+        .lot_ingredient_idx()
+        .filter(movement.ingredient_id)
+        .filter(|l| l.outlet_id == movement.outlet_id && l.quantity_remaining > 0)
+        .partition(|l| allow_expired || !lot_is_expired(ctx, l));
+    let mut lots = usable;
+    lots.sort_by_key(|l| (l.expires_at.is_none(), l.expires_at, l.received_at));
+    let usable_quantity: i64 = lots.iter().map(|l| l.quantity_remaining).sum();
+    // Stock that is not in any lot (e.g. opening stock) can still cover the rest.
+    let expired_quantity: i64 = expired.iter().map(|l| l.quantity_remaining).sum();
+    if quantity > usable_quantity && expired_quantity > movement.balance_after {
+        let name = ctx.db.ingredients().id().find(movement.ingredient_id).map(|g| g.name).unwrap_or_default();
+        return Err(format!("Only expired stock of {} is left; record it as waste first", name));
+    }
+    let mut left = quantity;
+    for mut lot in lots {
+        if left == 0 {
+            break;
+        }
+        let take = left.min(lot.quantity_remaining);
+        lot.quantity_remaining -= take;
+        if lot.quantity_remaining == 0 {
+            lot.status = LotStatus::Depleted;
+        }
+        ctx.db.lot_movements().insert(LotMovement {
+            id: 0,
+            lot_id: lot.id,
+            movement_id: movement.id,
+            quantity: -take,
+        });
+        ctx.db.stock_lots().id().update(lot);
+        left -= take;
+    }
+    Ok(())
+}
+
+// Posts a Purchase movement for the receipt and opens a lot for it.
+fn post_receipt(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    receipt: &StockReceiptInput,
+    supplier_id: Option<u64>,
+    reference: &str,
+) -> Result<StockMovement, String> {
+    if receipt.quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    if receipt.lot_number.trim().is_empty() {
+        return Err("Lot number is required".into());
+    }
+    let ing = ctx.db.ingredients().id().find(receipt.ingredient_id).ok_or("Ingredient not found")?;
+    let unit = ctx.db.units_of_measure().id().find(receipt.unit_id).ok_or("Unit not found")?;
+    let quantity = to_base_quantity(ctx, &ing, receipt.unit_id, receipt.quantity)?;
+    if receipt.price < 0 {
+        return Err("Price must not be negative".into());
+    }
+    let unit_cost = receipt.price / unit.factor;
+    let st = ensure_outlet_stock(ctx, ing.id, outlet_id)?;
+    let movement =
+        post_stock_movement(ctx, st.id, quantity, StockMovementType::Purchase, reference, "", Some(unit_cost))?;
+    create_lot(ctx, &movement, receipt.lot_number.clone(), supplier_id, None, receipt.expires_at);
+    Ok(movement)
+}
+
+#[reducer]
+pub fn receive_stock(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    receipt: StockReceiptInput,
+    supplier_id: Option<u64>,
+    reference: String,
+) -> Result<(), String> {
+    if let Some(sid) = supplier_id {
+        if ctx.db.suppliers().id().find(sid).is_none() {
+            return Err("Supplier not found".into());
+        }
+    }
+    post_receipt(ctx, outlet_id, &receipt, supplier_id, &reference)?;
+    Ok(())
+}
+
+#[reducer]
+pub fn flag_expiring_lots(ctx: &ReducerContext, _schedule: LotExpirySchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("flag_expiring_lots may only be invoked by the scheduler".into());
+    }
+    let horizon = ctx
+        .timestamp
+        .checked_add(TimeDuration::from_duration(Duration::from_secs(NEAR_EXPIRY_DAYS * 86400)))
+        .unwrap_or(ctx.timestamp);
+    let lots: Vec<StockLot> = ctx
+        .db
+        .stock_lots()
+        .iter()
+        .filter(|l| matches!(l.status, LotStatus::Active | LotStatus::NearExpiry))
+        .collect();
+    for mut lot in lots {
+        let Some(expires_at) = lot.expires_at else {
+            continue;
+        };
+        let status = if expires_at <= ctx.timestamp {
+            LotStatus::Expired
+        } else if expires_at <= horizon {
+            LotStatus::NearExpiry
+        } else {
+            continue;
+        };
+        if lot.status != status {
+            log::info!("Lot {} ({}) at outlet {} is now {:?}", lot.id, lot.lot_number, lot.outlet_id, status);
+            lot.status = status;
+            ctx.db.stock_lots().id().update(lot);
+        }
+    }
+    Ok(())
+}
+
+// Stock alerts
+
+fn low_stock_severity(stock: i64, min_stock: i64) -> Option<AlertSeverity> {
+    if stock >= min_stock {
+        None
+    } else if stock < min_stock / 2 {
+        Some(AlertSeverity::Critical)
+    } else {
+        Some(AlertSeverity::Warning)
+    }
+}
+
+// Opens, escalates or resolves the alert for a stock record. Called after every stock change.
+fn check_stock_alert(ctx: &ReducerContext, stock_id: u64) {
+    let Some(st) = ctx.db.ingredient_stock().id().find(stock_id) else {
+        return;
+    };
+    let open = ctx
+        .db
+        .stock_alerts()
+        .alert_ingredient_idx()
+        .filter(st.ingredient_id)
+        .find(|a| a.outlet_id == st.outlet_id && a.resolved_at.is_none());
+    match (open, low_stock_severity(st.stock, st.min_stock)) {
+        (Some(mut a), None) => {
+            a.stock = st.stock;
+            a.resolved_at = Some(ctx.timestamp);
+            ctx.db.stock_alerts().id().update(a);
+        }
+        (Some(mut a), Some(severity)) => {
+            // An escalation needs to be acknowledged again.
+            if a.severity == AlertSeverity::Warning && severity == AlertSeverity::Critical {
+                a.acknowledged = false;
+                a.acknowledged_by = None;
+            }
+            a.severity = severity;
+            a.stock = st.stock;
+            a.min_stock = st.min_stock;
+            ctx.db.stock_alerts().id().update(a);
+        }
+        (None, Some(severity)) => {
+            ctx.db.stock_alerts().insert(StockAlert {
+                id: 0,
+                ingredient_id: st.ingredient_id,
+                outlet_id: st.outlet_id,
+                severity,
+                stock: st.stock,
+                min_stock: st.min_stock,
+                acknowledged: false,
+                acknowledged_by: None,
+                created_at: ctx.timestamp,
+                resolved_at: None,
+            });
+        }
+        (None, None) => {}
+    }
+}
+
+#[reducer]
+pub fn acknowledge_stock_alert(ctx: &ReducerContext, alert_id: u64) -> Result<(), String> {
+    if let Some(mut a) = ctx.db.stock_alerts().id().find(alert_id) {
+        a.acknowledged = true;
+        a.acknowledged_by = Some(ctx.sender);
+        ctx.db.stock_alerts().id().update(a);
+        Ok(())
+    } else {
+        Err("Alert not found".into())
+    }
+}
+
+// Waste
+
+// Posts the waste movement and adds it to the summary. Returns the value written off, in cents.
+fn post_waste(
+    ctx: &ReducerContext,
+    stock: &IngredientStock,
+    quantity: i64,
+    reason: &WasteReason,
+    record_id: u64,
+) -> Result<i64, String> {
+    let reference = format!("waste:{}", record_id);
+    let reason = format!("{:?}", reason);
+    let movement = post_stock_movement(ctx, stock.id, -quantity, StockMovementType::Waste, &reference, &reason, None)?;
+    let ingredient_id = stock.ingredient_id;
+    let existing = ctx
+        .db
+        .waste_summary()
+        .wastesum_outlet_idx()
+        .filter(movement.outlet_id)
+        .find(|w| w.ingredient_id == ingredient_id);
+    if let Some(mut w) = existing {
+        w.quantity += quantity;
+        w.value -= movement.value;
+        w.incidents += 1;
+        ctx.db.waste_summary().id().update(w);
+    } else {
+        ctx.db.waste_summary().insert(WasteSummary {
+            id: 0,
+            outlet_id: movement.outlet_id,
+            ingredient_id,
+            quantity,
+            value: -movement.value,
+            incidents: 1,
+        });
+    }
+    Ok(-movement.value)
+}
+
+#[reducer]
+pub fn record_ingredient_waste(
+    ctx: &ReducerContext,
+    ingredient_id: u64,
+    outlet_id: u64,
+    quantity: i64,
+    reason: WasteReason,
+    reported_cost: i64,
+    notes: String,
+) -> Result<(), String> {
+    if quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    if reported_cost < 0 {
+        return Err("Reported cost must not be negative".into());
+    }
+    let st = require_outlet_stock(ctx, ingredient_id, outlet_id)?;
+    let record = ctx.db.waste_records().insert(WasteRecord {
+        id: 0,
+        outlet_id,
+        ingredient_id: Some(ingredient_id),
+        product_id: None,
+        quantity,
+        reason: reason.clone(),
+        cost: 0,
+        reported_cost,
+        notes,
+        recorded_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    let cost = post_waste(ctx, &st, quantity, &reason, record.id)?;
+    ctx.db.waste_records().id().update(WasteRecord { cost, ..record });
+    Ok(())
+}
+
+#[reducer]
+pub fn record_product_waste(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    product_id: u64,
+    quantity: i64,
+    reason: WasteReason,
+    reported_cost: i64,
+    notes: String,
+) -> Result<(), String> {
+    if quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    if reported_cost < 0 {
+        return Err("Reported cost must not be negative".into());
+    }
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    if !product_sold_at(ctx, product_id, outlet_id) {
+        return Err(format!("Product {} is not on the menu at this outlet", product_id));
+    }
+    let record = ctx.db.waste_records().insert(WasteRecord {
+        id: 0,
+        outlet_id,
+        ingredient_id: None,
+        product_id: Some(product_id),
+        quantity,
+        reason: reason.clone(),
+        cost: 0,
+        reported_cost,
+        notes,
+        recorded_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    let lines: Vec<Recipe> = ctx.db.recipes().recipe_product_idx().filter(product_id).collect();
+    let mut cost = 0i64;
+    for r in lines {
+        let st = require_outlet_stock(ctx, r.ingredient_id, outlet_id)?;
+        let value = post_waste(ctx, &st, r.quantity.saturating_mul(quantity), &reason, record.id)?;
+        cost = cost.saturating_add(value);
+    }
+    ctx.db.waste_records().id().update(WasteRecord { cost, ..record });
+    Ok(())
+}
+
+// Production
+
+#[reducer]
+pub fn create_production_order(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    output_ingredient_id: u64,
+    output_unit_id: u64,
+    planned_quantity: i64,
+    inputs: Vec<ProductionInputLine>,
+) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    if planned_quantity <= 0 {
+        return Err("Planned quantity must be positive".into());
+    }
+    if inputs.is_empty() {
+        return Err("Production order has no inputs".into());
+    }
+    let output = ctx.db.ingredients().id().find(output_ingredient_id).ok_or("Ingredient not found")?;
+    let planned_quantity = to_base_quantity(ctx, &output, output_unit_id, planned_quantity)?;
+    let mut lines = Vec::with_capacity(inputs.len());
+    for line in inputs.iter() {
+        if line.quantity <= 0 {
+            return Err("Quantity must be positive".into());
+        }
+        if line.ingredient_id == output_ingredient_id {
+            return Err("An ingredient cannot be an input to its own production".into());
+        }
+        let ing = ctx.db.ingredients().id().find(line.ingredient_id).ok_or("Ingredient not found")?;
+        lines.push((ing.id, to_base_quantity(ctx, &ing, line.unit_id, line.quantity)?));
+    }
+    let order = ctx.db.production_orders().insert(ProductionOrder {
+        id: 0,
+        outlet_id,
+        output_ingredient_id,
+        planned_quantity,
+        actual_quantity: 0,
+        total_cost: 0,
+        unit_cost: 0,
+        lot_id: None,
+        status: ProductionStatus::Planned,
+        created_by: ctx.sender,
+        created_at: ctx.timestamp,
+        completed_at: None,
+    });
+    for (ingredient_id, quantity) in lines {
+        ctx.db.production_inputs().insert(ProductionInput {
+            id: 0,
+            order_id: order.id,
+            ingredient_id,
+            quantity,
+            cost: 0,
+        });
+    }
+    Ok(())
+}
+
+// Consumes the inputs and receives the actual output as a new lot, costed from the inputs.
+// The actual quantity is given in unit_id, like the planned quantity on creation.
+#[reducer]
+pub fn complete_production_order(
+    ctx: &ReducerContext,
+    order_id: u64,
+    unit_id: u64,
+    actual_quantity: i64,
+    lot_number: String,
+    expires_at: Option<Timestamp>,
+) -> Result<(), String> {
+    let mut order = ctx.db.production_orders().id().find(order_id).ok_or("Production order not found")?;
+    if order.status != ProductionStatus::Planned {
+        return Err(format!("Production order is {:?}", order.status));
+    }
+    if actual_quantity <= 0 {
+        return Err("Actual quantity must be positive".into());
+    }
+    if lot_number.trim().is_empty() {
+        return Err("Lot number is required".into());
+    }
+    let output = ctx.db.ingredients().id().find(order.output_ingredient_id).ok_or("Ingredient not found")?;
+    let actual_quantity = to_base_quantity(ctx, &output, unit_id, actual_quantity)?;
+    let reference = format!("production:{}", order_id);
+    let mut total_cost = 0i64;
+    let inputs: Vec<ProductionInput> = ctx.db.production_inputs().prodinput_order_idx().filter(order_id).collect();
+    for mut input in inputs {
+        let st = require_outlet_stock(ctx, input.ingredient_id, order.outlet_id)?;
+        let movement =
+            post_stock_movement(ctx, st.id, -input.quantity, StockMovementType::ProductionOut, &reference, "", None)?;
+        input.cost = -movement.value;
+        total_cost = total_cost.saturating_add(input.cost);
+        ctx.db.production_inputs().id().update(input);
+    }
+    let unit_cost = total_cost / actual_quantity;
+    let st = ensure_outlet_stock(ctx, order.output_ingredient_id, order.outlet_id)?;
+    let movement =
+        post_stock_movement(ctx, st.id, actual_quantity, StockMovementType::ProductionIn, &reference, "", Some(unit_cost))?;
+    let lot = create_lot(ctx, &movement, lot_number, None, None, expires_at);
+    if actual_quantity != order.planned_quantity {
+        log::info!(
+            "Production order {} yielded {} of {} planned",
+            order_id,
+            actual_quantity,
+            order.planned_quantity
+        );
+    }
+    order.actual_quantity = actual_quantity;
+    order.total_cost = total_cost;
+    order.unit_cost = unit_cost;
+    order.lot_id = Some(lot.id);
+    order.status = ProductionStatus::Completed;
+    order.completed_at = Some(ctx.timestamp);
+    ctx.db.production_orders().id().update(order);
+    Ok(())
+}
+
+#[reducer]
+pub fn cancel_production_order(ctx: &ReducerContext, order_id: u64) -> Result<(), String> {
+    let mut order = ctx.db.production_orders().id().find(order_id).ok_or("Production order not found")?;
+    if order.status != ProductionStatus::Planned {
+        return Err(format!("Production order is {:?}", order.status));
+    }
+    order.status = ProductionStatus::Cancelled;
+    ctx.db.production_orders().id().update(order);
+    Ok(())
+}
+
+// Stock counts
+
+fn active_count_session(ctx: &ReducerContext, outlet_id: u64) -> Option<StockCountSession> {
+    ctx.db
+        .stock_count_sessions()
+        .count_outlet_idx()
+        .filter(outlet_id)
+        .find(|s| matches!(s.status, CountSessionStatus::Open | CountSessionStatus::Review))
+}
+
+#[reducer]
+pub fn start_stock_count(ctx: &ReducerContext, outlet_id: u64) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    if let Some(s) = active_count_session(ctx, outlet_id) {
+        return Err(format!("Stock count {} is already in progress at this outlet", s.id));
+    }
+    let session = ctx.db.stock_count_sessions().insert(StockCountSession {
+        id: 0,
+        outlet_id,
+        status: CountSessionStatus::Open,
+        started_by: ctx.sender,
+        started_at: ctx.timestamp,
+        approved_by: None,
+        approved_at: None,
+        variance_value: 0,
+    });
+    for st in ctx.db.ingredient_stock().stock_outlet_idx().filter(outlet_id) {
+        ctx.db.stock_count_lines().insert(StockCountLine {
+            id: 0,
+            session_id: session.id,
+            ingredient_id: st.ingredient_id,
+            system_qty: st.stock,
+            counted_qty: None,
+            variance: 0,
+            variance_value: 0,
+        });
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn record_stock_count(
+    ctx: &ReducerContext,
+    session_id: u64,
+    ingredient_id: u64,
+    location: String,
+    quantity: i64,
+) -> Result<(), String> {
+    let session = ctx.db.stock_count_sessions().id().find(session_id).ok_or("Stock count not found")?;
+    if session.status != CountSessionStatus::Open {
+        return Err("Stock count is not open for counting".into());
+    }
+    if quantity < 0 {
+        return Err("Counted quantity must not be negative".into());
+    }
+    let mut line = ctx
+        .db
+        .stock_count_lines()
+        .countline_session_idx()
+        .filter(session_id)
+        .find(|l| l.ingredient_id == ingredient_id)
+        .ok_or("Ingredient is not part of this stock count")?;
+    let existing = ctx
+        .db
+        .stock_count_entries()
+        .countentry_line_idx()
+        .filter(line.id)
+        .find(|e| e.counted_by == ctx.sender && e.location == location);
+    if let Some(mut e) = existing {
+        e.quantity = quantity;
+        e.counted_at = ctx.timestamp;
+        ctx.db.stock_count_entries().id().update(e);
+    } else {
+        ctx.db.stock_count_entries().insert(StockCountEntry {
+            id: 0,
+            line_id: line.id,
+            location,
+            quantity,
+            counted_by: ctx.sender,
+            counted_at: ctx.timestamp,
+        });
+    }
+    let counted: i64 = ctx.db.stock_count_entries().countentry_line_idx().filter(line.id).map(|e| e.quantity).sum();
+    line.counted_qty = Some(counted);
+    line.variance = counted - line.system_qty;
+    ctx.db.stock_count_lines().id().update(line);
+    Ok(())
+}
+
+#[reducer]
+pub fn submit_stock_count(ctx: &ReducerContext, session_id: u64) -> Result<(), String> {
+    let mut session = ctx.db.stock_count_sessions().id().find(session_id).ok_or("Stock count not found")?;
+    if session.status != CountSessionStatus::Open {
+        return Err("Stock count is not open".into());
+    }
+    let uncounted = ctx
+        .db
+        .stock_count_lines()
+        .countline_session_idx()
+        .filter(session_id)
+        .filter(|l| l.counted_qty.is_none())
+        .count();
+    if uncounted > 0 {
+        return Err(format!("{} ingredients have not been counted yet", uncounted));
+    }
+    session.status = CountSessionStatus::Review;
+    ctx.db.stock_count_sessions().id().update(session);
+    Ok(())
+}
+
+// Sets each counted ingredient to what the count found, carried forward by the movements
+// posted after it was counted, and posts the difference from current stock as a Count
+// movement. Movements made while the count was open are flagged with the session id.
+#[reducer]
+pub fn approve_stock_count(ctx: &ReducerContext, session_id: u64) -> Result<(), String> {
+    let mut session = ctx.db.stock_count_sessions().id().find(session_id).ok_or("Stock count not found")?;
+    if session.status != CountSessionStatus::Review {
+        return Err("Stock count must be submitted for review before approval".into());
+    }
+    session.status = CountSessionStatus::Approved;
+    session.approved_by = Some(ctx.sender);
+    session.approved_at = Some(ctx.timestamp);
+    let outlet_id = session.outlet_id;
+    // The period runs from the previous approved count, or from the outlet's first movement,
+    // which carries its opening stock.
+    let period_start = ctx
+        .db
+        .stock_count_sessions()
+        .count_outlet_idx()
+        .filter(outlet_id)
+        .filter(|s| s.id != session_id && s.status == CountSessionStatus::Approved)
+        .filter_map(|s| s.approved_at)
+        .max()
+        .or_else(|| ctx.db.stock_movements().movement_outlet_idx().filter(outlet_id).map(|m| m.date).min())
+        .unwrap_or(Timestamp::UNIX_EPOCH);
+    let reference = format!("count:{}", session_id);
+    let lines: Vec<StockCountLine> = ctx.db.stock_count_lines().countline_session_idx().filter(session_id).collect();
+    for mut l in lines {
+        let st = require_outlet_stock(ctx, l.ingredient_id, outlet_id)?;
+        let counted_at = ctx
+            .db
+            .stock_count_entries()
+            .countentry_line_idx()
+            .filter(l.id)
+            .map(|e| e.counted_at)
+            .max()
+            .unwrap_or(session.started_at);
+        let since_count: i64 = ctx
+            .db
+            .stock_movements()
+            .movement_ingredient_idx()
+            .filter(l.ingredient_id)
+            .filter(|m| m.outlet_id == outlet_id && m.count_session_id == Some(session_id) && m.date > counted_at)
+            .map(|m| m.delta)
+            .sum();
+        let target = (l.counted_qty.unwrap_or(0) + since_count).max(0);
+        l.variance = target - st.stock;
+        if l.variance == 0 {
+            ctx.db.stock_count_lines().id().update(l);
+            continue;
+        }
+        let movement =
+            post_stock_movement(ctx, st.id, l.variance, StockMovementType::Count, &reference, "Stock opname", None)?;
+        l.variance_value = movement.value;
+        session.variance_value = session.variance_value.saturating_add(movement.value);
+        ctx.db.stock_count_lines().id().update(l);
+    }
+    ctx.db.stock_count_sessions().id().update(session);
+    build_usage_variance(ctx, outlet_id, period_start, ctx.timestamp, Some(session_id))?;
+    Ok(())
+}
+
+#[reducer]
+pub fn cancel_stock_count(ctx: &ReducerContext, session_id: u64) -> Result<(), String> {
+    let mut session = ctx.db.stock_count_sessions().id().find(session_id).ok_or("Stock count not found")?;
+    if !matches!(session.status, CountSessionStatus::Open | CountSessionStatus::Review) {
+        return Err("Stock count is already closed".into());
+    }
+    session.status = CountSessionStatus::Cancelled;
+    ctx.db.stock_count_sessions().id().update(session);
+    Ok(())
+}
+
+// Usage variance
+
+#[derive(Default)]
+struct UsageTotals {
+    after_start: i64,
+    after_end: i64,
+    received: i64,
+    transferred_in: i64,
+    transferred_out: i64,
+    theoretical: i64,
+    waste: i64,
+    // quantity and value of the period's outbound movements, for the period's unit cost
+    out_quantity: i64,
+    out_value: i64,
+}
+
+fn build_usage_variance(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    period_start: Timestamp,
+    period_end: Timestamp,
+    count_session_id: Option<u64>,
+) -> Result<UsageVarianceReport, String> {
+    if period_end <= period_start {
+        return Err("Period end must be after period start".into());
+    }
+    if period_end > ctx.timestamp {
+        return Err("Period end must not be in the future".into());
+    }
+    let mut totals: BTreeMap<u64, UsageTotals> = BTreeMap::new();
+    for m in ctx.db.stock_movements().movement_outlet_idx().filter(outlet_id) {
+        if m.date <= period_start {
+            continue;
+        }
+        let t = totals.entry(m.ingredient_id).or_default();
+        t.after_start += m.delta;
+        if m.date > period_end {
+            t.after_end += m.delta;
+            continue;
+        }
+        if m.delta < 0 {
+            t.out_quantity -= m.delta;
+            t.out_value -= m.value;
+        }
+        match m.movement_type {
+            StockMovementType::Purchase | StockMovementType::ProductionIn | StockMovementType::PurchaseReturn => {
+                t.received += m.delta
+            }
+            StockMovementType::TransferIn => t.transferred_in += m.delta,
+            StockMovementType::TransferOut => t.transferred_out -= m.delta,
+            StockMovementType::Sale | StockMovementType::ProductionOut => t.theoretical -= m.delta,
+            StockMovementType::Waste => t.waste -= m.delta,
+            // adjustments and count corrections only show up through the closing balance
+            StockMovementType::Adjustment | StockMovementType::Count => {}
+        }
+    }
+    let mut report = ctx.db.usage_variance_reports().insert(UsageVarianceReport {
+        id: 0,
+        outlet_id,
+        period_start,
+        period_end,
+        count_session_id,
+        theoretical_value: 0,
+        actual_value: 0,
+        variance_value: 0,
+        generated_by: ctx.sender,
+        generated_at: ctx.timestamp,
+    });
+    for st in ctx.db.ingredient_stock().stock_outlet_idx().filter(outlet_id) {
+        let t = totals.remove(&st.ingredient_id).unwrap_or_default();
+        let opening = st.stock - t.after_start;
+        let closing = st.stock - t.after_end;
+        let actual = opening + t.received + t.transferred_in - t.transferred_out - closing;
+        if opening == 0 && closing == 0 && actual == 0 && t.theoretical == 0 {
+            continue;
+        }
+        let variance = actual - t.theoretical - t.waste;
+        let unit_cost = if t.out_quantity > 0 { t.out_value / t.out_quantity } else { st.avg_cost };
+        let variance_value = variance.saturating_mul(unit_cost);
+        report.theoretical_value = report.theoretical_value.saturating_add(t.theoretical.saturating_mul(unit_cost));
+        report.actual_value = report.actual_value.saturating_add(actual.saturating_mul(unit_cost));
+        report.variance_value = report.variance_value.saturating_add(variance_value);
+        ctx.db.usage_variance_lines().insert(UsageVarianceLine {
+            id: 0,
+            report_id: report.id,
+            ingredient_id: st.ingredient_id,
+            opening,
+            received: t.received,
+            transferred_in: t.transferred_in,
+            transferred_out: t.transferred_out,
+            closing,
+            actual,
+            theoretical: t.theoretical,
+            waste: t.waste,
+            variance,
+            variance_value,
+        });
+    }
+    Ok(ctx.db.usage_variance_reports().id().update(report))
+}
+
+#[reducer]
+pub fn generate_usage_variance_report(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    period_start: Timestamp,
+    period_end: Timestamp,
+) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    build_usage_variance(ctx, outlet_id, period_start, period_end, None)?;
+    Ok(())
+}
+
+// Recipes and availability
+
+fn product_sold_at(ctx: &ReducerContext, product_id: u64, outlet_id: u64) -> bool {
+    find_menu_item(ctx, outlet_id, product_id).is_some_and(|m| m.enabled)
+}
+
+fn find_availability(ctx: &ReducerContext, outlet_id: u64, product_id: u64) -> Option<ProductAvailability> {
+    ctx.db.product_availability().avail_outlet_idx().filter(outlet_id).find(|a| a.product_id == product_id)
+}
+
+fn refresh_product_availability(ctx: &ReducerContext, outlet_id: u64, product_id: u64) {
+    let mut computed = AvailabilityStatus::Available;
+    let mut reasons = Vec::new();
+    for r in ctx.db.recipes().recipe_product_idx().filter(product_id) {
+        let Some(ing) = ctx.db.ingredients().id().find(r.ingredient_id) else {
+            continue;
+        };
+        match outlet_stock(ctx, r.ingredient_id, outlet_id) {
+            Some(st) if ing.status == IngredientStatus::Active && st.stock >= r.quantity => {
+                if st.stock - r.quantity < st.min_stock {
+                    if computed == AvailabilityStatus::Available {
+                        computed = AvailabilityStatus::Low;
+                    }
+                    reasons.push(format!("{} below min stock", ing.name));
+                }
+            }
+            Some(_) => {
+                computed = AvailabilityStatus::SoldOut;
+                reasons.push(format!("{} out of stock", ing.name));
+            }
+            None => {
+                computed = AvailabilityStatus::SoldOut;
+                reasons.push(format!("{} not stocked", ing.name));
+            }
+        }
+    }
+    let reason = reasons.join(", ");
+    match find_availability(ctx, outlet_id, product_id) {
+        Some(mut a) => {
+            if a.computed_status == computed && a.reason == reason {
+                return;
+            }
+            a.status = a.manual_status.clone().unwrap_or_else(|| computed.clone());
+            a.computed_status = computed;
+            a.reason = reason;
+            a.updated_at = ctx.timestamp;
+            ctx.db.product_availability().id().update(a);
+        }
+        None => {
+            ctx.db.product_availability().insert(ProductAvailability {
+                id: 0,
+                outlet_id,
+                product_id,
+                computed_status: computed.clone(),
+                manual_status: None,
+                status: computed,
+                reason,
+                manual_reason: None,
+                updated_at: ctx.timestamp,
+            });
+        }
+    }
+}
+
+// Recomputes every product at the outlet whose recipe uses the ingredient.
+fn refresh_availability_for_ingredient(ctx: &ReducerContext, ingredient_id: u64, outlet_id: u64) {
+    let mut product_ids: Vec<u64> =
+        ctx.db.recipes().recipe_ingredient_idx().filter(ingredient_id).map(|r| r.product_id).collect();
+    product_ids.sort_unstable();
+    product_ids.dedup();
+    for product_id in product_ids {
+        if product_sold_at(ctx, product_id, outlet_id) {
+            refresh_product_availability(ctx, outlet_id, product_id);
+        }
+    }
+}
+// Recomputes the product at every outlet that sells it.
+fn refresh_availability_for_product(ctx: &ReducerContext, product_id: u64) {
+    let outlet_ids: Vec<u64> = ctx
+        .db
+        .outlet_menu_items()
+        .menu_product_idx()
+        .filter(product_id)
+        .filter(|m| m.enabled)
+        .map(|m| m.outlet_id)
+        .collect();
+    for outlet_id in outlet_ids {
+        refresh_product_availability(ctx, outlet_id, product_id);
+    }
+}
+
+#[reducer]
+pub fn set_recipe_item(
+    ctx: &ReducerContext,
+    product_id: u64,
+    ingredient_id: u64,
+    quantity: i64,
+) -> Result<(), String> {
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    if ctx.db.ingredients().id().find(ingredient_id).is_none() {
+        return Err("Ingredient not found".into());
+    }
+    if quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    let existing = ctx
+        .db
+        .recipes()
+        .recipe_product_idx()
+        .filter(product_id)
+        .find(|r| r.ingredient_id == ingredient_id);
+    if let Some(mut r) = existing {
+        r.quantity = quantity;
+        ctx.db.recipes().id().update(r);
+    } else {
+        ctx.db.recipes().insert(Recipe {
+            id: 0,
+            product_id,
+            ingredient_id,
+            quantity,
+        });
+    }
+    refresh_availability_for_product(ctx, product_id);
+    Ok(())
+}
+
+#[reducer]
+pub fn remove_recipe_item(ctx: &ReducerContext, recipe_id: u64) -> Result<(), String> {
+    let r = ctx.db.recipes().id().find(recipe_id).ok_or("Recipe item not found")?;
+    ctx.db.recipes().id().delete(recipe_id);
+    refresh_availability_for_product(ctx, r.product_id);
+    Ok(())
+}
+
+#[reducer]
+pub fn set_product_availability(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    product_id: u64,
+    manual_status: Option<AvailabilityStatus>,
+    reason: String,
+) -> Result<(), String> {
+    if !product_sold_at(ctx, product_id, outlet_id) {
+        return Err("Product is not sold at this outlet".into());
+    }
+    refresh_product_availability(ctx, outlet_id, product_id);
+    let mut a = find_availability(ctx, outlet_id, product_id).ok_or("Availability not found")?;
+    a.status = manual_status.clone().unwrap_or_else(|| a.computed_status.clone());
+    a.manual_reason = manual_status.as_ref().map(|_| reason);
+    a.manual_status = manual_status;
+    a.updated_at = ctx.timestamp;
+    ctx.db.product_availability().id().update(a);
+    Ok(())
+}
+
+// Purchasing
+
+#[reducer]
+pub fn add_supplier(
+    ctx: &ReducerContext,
+    name: String,
+    contact: String,
+    payment_terms_days: u32,
+    lead_time_days: u32,
+) -> Result<(), String> {
+    // rated by the scoring job once there is purchasing history
+    ctx.db.suppliers().insert(Supplier {
+        id: 0,
+        name,
+        contact,
+        rating: 0,
+        payment_terms_days,
+        lead_time_days,
+    });
+    Ok(())
+}
+
+fn expected_delivery(ctx: &ReducerContext, supplier_id: u64, order_date: Timestamp) -> Timestamp {
+    let lead_time = ctx.db.suppliers().id().find(supplier_id).map(|s| s.lead_time_days).unwrap_or(0);
+    order_date
+        .checked_add(TimeDuration::from_duration(Duration::from_secs(lead_time as u64 * 86400)))
+        .unwrap_or(order_date)
+}
+
+// Starts a new catalog price. The current price for the same supplier, ingredient and
+// unit ends where the new one begins.
+#[reducer]
+pub fn set_supplier_price(
+    ctx: &ReducerContext,
+    supplier_id: u64,
+    ingredient_id: u64,
+    unit_id: u64,
+    price: i64,
+    effective_from: Timestamp,
+) -> Result<(), String> {
+    if ctx.db.suppliers().id().find(supplier_id).is_none() {
+        return Err("Supplier not found".into());
+    }
+    let ing = ctx.db.ingredients().id().find(ingredient_id).ok_or("Ingredient not found")?;
+    // validates that the unit fits the ingredient
+    to_base_quantity(ctx, &ing, unit_id, 1)?;
+    if price <= 0 {
+        return Err("Price must be positive".into());
+    }
+    let current = ctx
+        .db
+        .supplier_prices()
+        .supprice_supplier_idx()
+        .filter(supplier_id)
+        .find(|p| p.ingredient_id == ingredient_id && p.unit_id == unit_id && p.effective_to.is_none());
+    if let Some(mut current) = current {
+        if effective_from <= current.effective_from {
+            return Err("New price must start after the current price".into());
+        }
+        current.effective_to = Some(effective_from);
+        ctx.db.supplier_prices().id().update(current);
+    }
+    ctx.db.supplier_prices().insert(SupplierPrice {
+        id: 0,
+        supplier_id,
+        ingredient_id,
+        unit_id,
+        price,
+        effective_from,
+        effective_to: None,
+        created_by: ctx.sender,
+        created_at: ctx.timestamp,
+    });
+    Ok(())
+}
+
+// Delists an item from the supplier's catalog without removing its history.
+#[reducer]
+pub fn end_supplier_price(ctx: &ReducerContext, price_id: u64, effective_to: Timestamp) -> Result<(), String> {
+    let mut p = ctx.db.supplier_prices().id().find(price_id).ok_or("Supplier price not found")?;
+    if p.effective_to.is_some() {
+        return Err("Supplier price has already ended".into());
+    }
+    if effective_to <= p.effective_from {
+        return Err("End must be after the start of the price".into());
+    }
+    p.effective_to = Some(effective_to);
+    ctx.db.supplier_prices().id().update(p);
+    Ok(())
+}
+
+// Value of a PO line in cents: the ordered quantity in the line's unit_id times the price per ordered unit.
+fn po_line_total(item: &PurchaseOrderItem) -> i64 {
+    item.ordered_quantity.saturating_mul(item.price)
+}
+
+// Allowed deviation from the catalog price before a PO line is flagged, in basis points.
+const PRICE_TOLERANCE_BPS: i64 = 500;
+
+fn catalog_price(
+    ctx: &ReducerContext,
+    supplier_id: u64,
+    ingredient_id: u64,
+    unit_id: u64,
+    at: Timestamp,
+) -> Option<SupplierPrice> {
+    ctx.db
+        .supplier_prices()
+        .supprice_supplier_idx()
+        .filter(supplier_id)
+        .filter(|p| p.ingredient_id == ingredient_id && p.unit_id == unit_id)
+        .filter(|p| p.effective_from <= at && p.effective_to.is_none_or(|to| at < to))
+        .max_by_key(|p| p.effective_from)
+}
+
+// Compares the line against the PO supplier's catalog on the PO date.
+fn apply_catalog_price(ctx: &ReducerContext, po: &PurchaseOrder, item: &mut PurchaseOrderItem) {
+    item.catalog_price =
+        catalog_price(ctx, po.supplier_id, item.ingredient_id, item.unit_id, po.date).map(|p| p.price);
+    item.price_flagged = match item.catalog_price {
+        Some(list) => (item.price - list).abs().saturating_mul(10_000) > list.saturating_mul(PRICE_TOLERANCE_BPS),
+        None => false,
+    };
+    if item.price_flagged {
+        log::warn!(
+            "PO {} line for ingredient {} priced at {} against catalog {:?}",
+            po.id,
+            item.ingredient_id,
+            item.price,
+            item.catalog_price
+        );
+    }
+}
+
+// Validates an input line for the PO. The returned item is not inserted yet.
+fn build_po_item(
+    ctx: &ReducerContext,
+    po: &PurchaseOrder,
+    input: &PurchaseOrderItemInput,
+) -> Result<PurchaseOrderItem, String> {
+    if input.quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    let ing = ctx.db.ingredients().id().find(input.ingredient_id).ok_or("Ingredient not found")?;
+    if ing.status != IngredientStatus::Active {
+        return Err(format!("{} is not active", ing.name));
+    }
+    if outlet_stock(ctx, ing.id, po.outlet_id).is_none() {
+        return Err(format!("{} is not stocked at outlet {}", ing.name, po.outlet_id));
+    }
+    let price = match input.price {
+        Some(price) => price,
+        None => catalog_price(ctx, po.supplier_id, ing.id, input.unit_id, po.date)
+            .map(|p| p.price)
+            .ok_or_else(|| format!("No catalog price for {} from this supplier; enter a price", ing.name))?,
+    };
+    if price < 0 {
+        return Err("Price must not be negative".into());
+    }
+    let mut item = PurchaseOrderItem {
+        id: 0,
+        purchase_order_id: po.id,
+        ingredient_id: ing.id,
+        unit_id: input.unit_id,
+        ordered_quantity: input.quantity,
+        quantity: to_base_quantity(ctx, &ing, input.unit_id, input.quantity)?,
+        received_quantity: 0,
+        price,
+        catalog_price: None,
+        price_flagged: false,
+    };
+    apply_catalog_price(ctx, po, &mut item);
+    Ok(item)
+}
+
+// The total is always the sum of the lines; it is never taken from the client.
+#[reducer]
+pub fn create_purchase_order(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    supplier_id: u64,
+    date: Timestamp,
+    items: Vec<PurchaseOrderItemInput>,
+) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    if ctx.db.suppliers().id().find(supplier_id).is_none() {
+        return Err("Supplier not found".into());
+    }
+    if items.is_empty() {
+        return Err("Purchase order has no items".into());
+    }
+    let mut po = ctx.db.purchase_orders().insert(PurchaseOrder {
+        id: 0,
+        outlet_id,
+        supplier_id,
+        total: 0,
+        status: POStatus::Created,
+        date,
+        expected_delivery: expected_delivery(ctx, supplier_id, date),
+        created_by: ctx.sender,
+        revision: 0,
+    });
+    for it in items.iter() {
+        let line = build_po_item(ctx, &po, it)?;
+        ctx.db.purchase_order_items().insert(line);
+    }
+    recompute_po_total(ctx, &mut po);
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+fn recompute_po_total(ctx: &ReducerContext, po: &mut PurchaseOrder) {
+    po.total = ctx
+        .db
+        .purchase_order_items()
+        .poi_po_idx()
+        .filter(po.id)
+        .fold(0i64, |sum, it| sum.saturating_add(po_line_total(&it)));
+}
+
+// POs can be edited freely only until they are ordered. Editing voids the approvals
+// collected so far, since they were given for a different PO.
+fn edit_draft_po(ctx: &ReducerContext, po_id: u64) -> Result<PurchaseOrder, String> {
+    let po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if po.status != POStatus::Created {
+        return Err(format!("Purchase order is {:?}; changes need an amendment", po.status));
+    }
+    if po_has_receipts(ctx, po_id) {
+        return Err("Purchase order has received goods; changes need an amendment".into());
+    }
+    let approvals: Vec<u64> = ctx.db.po_approvals().poapproval_po_idx().filter(po_id).map(|a| a.id).collect();
+    for id in approvals {
+        ctx.db.po_approvals().id().delete(id);
+    }
+    Ok(po)
+}
+
+#[reducer]
+pub fn update_purchase_order(
+    ctx: &ReducerContext,
+    po_id: u64,
+    supplier_id: u64,
+    date: Timestamp,
+) -> Result<(), String> {
+    let mut po = edit_draft_po(ctx, po_id)?;
+    if ctx.db.suppliers().id().find(supplier_id).is_none() {
+        return Err("Supplier not found".into());
+    }
+    po.supplier_id = supplier_id;
+    po.date = date;
+    po.expected_delivery = expected_delivery(ctx, supplier_id, date);
+    let items: Vec<PurchaseOrderItem> = ctx.db.purchase_order_items().poi_po_idx().filter(po_id).collect();
+    for mut it in items {
+        apply_catalog_price(ctx, &po, &mut it);
+        ctx.db.purchase_order_items().id().update(it);
+    }
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+#[reducer]
+pub fn add_purchase_order_item(ctx: &ReducerContext, po_id: u64, item: PurchaseOrderItemInput) -> Result<(), String> {
+    let mut po = edit_draft_po(ctx, po_id)?;
+    let line = build_po_item(ctx, &po, &item)?;
+    ctx.db.purchase_order_items().insert(line);
+    recompute_po_total(ctx, &mut po);
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+#[reducer]
+pub fn update_purchase_order_item(
+    ctx: &ReducerContext,
+    item_id: u64,
+    item: PurchaseOrderItemInput,
+) -> Result<(), String> {
+    let existing = ctx.db.purchase_order_items().id().find(item_id).ok_or("Purchase order item not found")?;
+    let mut po = edit_draft_po(ctx, existing.purchase_order_id)?;
+    let line = build_po_item(ctx, &po, &item)?;
+    ctx.db.purchase_order_items().id().update(PurchaseOrderItem { id: item_id, ..line });
+    recompute_po_total(ctx, &mut po);
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+#[reducer]
+pub fn remove_purchase_order_item(ctx: &ReducerContext, item_id: u64) -> Result<(), String> {
+    let existing = ctx.db.purchase_order_items().id().find(item_id).ok_or("Purchase order item not found")?;
+    let mut po = edit_draft_po(ctx, existing.purchase_order_id)?;
+    if ctx.db.purchase_order_items().poi_po_idx().filter(po.id).count() == 1 {
+        return Err("A purchase order needs at least one item; reject it instead".into());
+    }
+    ctx.db.purchase_order_items().id().delete(item_id);
+    recompute_po_total(ctx, &mut po);
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+// Changes the lines of an ordered PO and records the change as a new revision.
+// Lines cannot drop below what has already been received. Approvals were given for the
+// old total, so if the total changes they are voided and the PO goes back to Created.
+// Only AdminPusat, area managers for the outlet's area and the outlet's own users may amend.
+#[reducer]
+pub fn amend_purchase_order(
+    ctx: &ReducerContext,
+    po_id: u64,
+    lines: Vec<PurchaseOrderAmendmentLineInput>,
+    reason: String,
+) -> Result<(), String> {
+    let mut po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if !matches!(po.status, POStatus::Ordered | POStatus::PartiallyReceived) {
+        return Err(format!("Cannot amend a purchase order that is {:?}", po.status));
+    }
+    let user = ctx.db.users().id().find(ctx.sender).ok_or("User not found")?;
+    check_po_approver_scope(ctx, &user, &po)?;
+    let outlet_user = user.outlet_id == Some(po.outlet_id);
+    if !matches!(user.role, UserRole::AdminPusat | UserRole::AreaManager) && !outlet_user {
+        return Err("Only users of the PO's outlet can amend it".into());
+    }
+    if lines.is_empty() {
+        return Err("Amendment has no lines".into());
+    }
+    if reason.trim().is_empty() {
+        return Err("Amendment reason is required".into());
+    }
+    po.revision += 1;
+    let amendment = ctx.db.purchase_order_amendments().insert(PurchaseOrderAmendment {
+        id: 0,
+        purchase_order_id: po_id,
+        revision: po.revision,
+        reason,
+        previous_total: po.total,
+        new_total: 0,
+        amended_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    for line in lines {
+        let previous = match line.po_item_id {
+            Some(item_id) => {
+                let it = ctx.db.purchase_order_items().id().find(item_id).ok_or("Purchase order item not found")?;
+                if it.purchase_order_id != po_id {
+                    return Err(format!("Item {} is not on purchase order {}", item_id, po_id));
+                }
+                if it.ingredient_id != line.item.ingredient_id {
+                    return Err("An amendment cannot change a line's ingredient; add a new line instead".into());
+                }
+                Some(it)
+            }
+            None => None,
+        };
+        let (item_id, new_quantity, new_price) = match &previous {
+            Some(it) if line.item.quantity == 0 => {
+                if it.received_quantity > 0 {
+                    return Err("Cannot remove a line that has already been received".into());
+                }
+                ctx.db.purchase_order_items().id().delete(it.id);
+                (it.id, 0, it.price)
+            }
+            Some(it) => {
+                let updated = build_po_item(ctx, &po, &line.item)?;
+                if updated.quantity < it.received_quantity {
+                    return Err("Quantity cannot be reduced below what has been received".into());
+                }
+                let (quantity, price) = (updated.quantity, updated.price);
+                ctx.db.purchase_order_items().id().update(PurchaseOrderItem {
+                    id: it.id,
+                    received_quantity: it.received_quantity,
+                    ..updated
+                });
+                (it.id, quantity, price)
+            }
+            None => {
+                let added = build_po_item(ctx, &po, &line.item)?;
+                let added = ctx.db.purchase_order_items().insert(added);
+                (added.id, added.quantity, added.price)
+            }
+        };
+        ctx.db.purchase_order_amendment_lines().insert(PurchaseOrderAmendmentLine {
             id: 0,
-            name: (*name).to_string(),
-            unit: (*unit).to_string(),
-            min_stock: min,
-            stock: cur,
-            outlet_id,
-            status: IngredientStatus::Active,
+            amendment_id: amendment.id,
+            po_item_id: item_id,
+            ingredient_id: line.item.ingredient_id,
+            previous_quantity: previous.as_ref().map(|it| it.quantity).unwrap_or(0),
+            new_quantity,
+            previous_price: previous.as_ref().map(|it| it.price).unwrap_or(0),
+            new_price,
         });
     }
-    let ingredient_ids: Vec<u64> = ctx.db.ingredients().iter().map(|g| g.id).collect();
+    let remaining: Vec<PurchaseOrderItem> = ctx.db.purchase_order_items().poi_po_idx().filter(po_id).collect();
+    if remaining.is_empty() {
+        return Err("A purchase order needs at least one item; close it instead".into());
+    }
+    recompute_po_total(ctx, &mut po);
+    if po.status == POStatus::PartiallyReceived && remaining.iter().all(|it| it.received_quantity >= it.quantity) {
+        po.status = POStatus::Received;
+    }
+    let approvals: Vec<PoApproval> = ctx.db.po_approvals().poapproval_po_idx().filter(po_id).collect();
+    if approvals.iter().any(|a| a.total != po.total) {
+        for a in &approvals {
+            ctx.db.po_approvals().id().delete(a.id);
+        }
+        po.status = POStatus::Created;
+    } else if required_approval_levels(ctx, po.total).iter().any(|l| !approvals.iter().any(|a| a.level == *l)) {
+        po.status = POStatus::Created;
+    }
+    ctx.db.purchase_order_amendments().id().update(PurchaseOrderAmendment {
+        new_total: po.total,
+        ..amendment
+    });
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
 
-    // 5) Employees (50)
-    let positions = ["Manager", "Koki", "Kasir", "Waiter"];
-    for i in 0..50 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let pos = positions[i as usize % positions.len()].to_string();
-        let salary = match pos.as_str() {
-            "Manager" => 6_000_000i64,
-            "Koki" => 4_000_000i64,
-            "Kasir" => 3_500_000i64,
-            _ => 3_000_000i64,
-        } * 100; // cents
-        ctx.db.employees().insert(Employee {
+fn po_has_receipts(ctx: &ReducerContext, po_id: u64) -> bool {
+    ctx.db.purchase_order_items().poi_po_idx().filter(po_id).any(|it| it.received_quantity > 0)
+}
+
+// Status a PO moves to once fully approved. A PO sent back for approval after an
+// amendment resumes from what has already been received.
+fn approved_po_status(ctx: &ReducerContext, po_id: u64) -> POStatus {
+    let items: Vec<PurchaseOrderItem> = ctx.db.purchase_order_items().poi_po_idx().filter(po_id).collect();
+    if !items.iter().any(|it| it.received_quantity > 0) {
+        POStatus::Ordered
+    } else if items.iter().all(|it| it.received_quantity >= it.quantity) {
+        POStatus::Received
+    } else {
+        POStatus::PartiallyReceived
+    }
+}
+
+// Outlet managers act on their own outlet's POs and area managers on POs of outlets in
+// their area (the area of the outlet they are assigned to).
+fn check_po_approver_scope(ctx: &ReducerContext, user: &User, po: &PurchaseOrder) -> Result<(), String> {
+    match user.role {
+        UserRole::OutletManager if user.outlet_id != Some(po.outlet_id) => {
+            Err("Outlet managers can only act on purchase orders for their own outlet".into())
+        }
+        UserRole::AreaManager => {
+            let po_area = ctx.db.outlets().id().find(po.outlet_id).map(|o| o.area);
+            let user_area = user.outlet_id.and_then(|id| ctx.db.outlets().id().find(id)).map(|o| o.area);
+            if user_area.is_none() || user_area != po_area {
+                return Err("Area managers can only act on purchase orders for outlets in their area".into());
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Approval levels a PO of this total has to pass, lowest first.
+fn required_approval_levels(ctx: &ReducerContext, total: i64) -> Vec<u32> {
+    let mut levels: Vec<u32> = ctx.db.po_approval_rules().iter().map(|r| r.level).collect();
+    levels.sort_unstable();
+    levels.dedup();
+    let mut required = Vec::new();
+    for level in levels {
+        required.push(level);
+        let covers = ctx
+            .db
+            .po_approval_rules()
+            .iter()
+            .any(|r| r.level == level && r.max_amount.is_none_or(|max| total <= max));
+        if covers {
+            break;
+        }
+    }
+    required
+}
+
+// Creates or updates the rule for a role at an approval level. Only AdminPusat may change rules.
+#[reducer]
+pub fn set_po_approval_rule(
+    ctx: &ReducerContext,
+    level: u32,
+    role: UserRole,
+    max_amount: Option<i64>,
+) -> Result<(), String> {
+    let user = ctx.db.users().id().find(ctx.sender).ok_or("User not found")?;
+    if user.role != UserRole::AdminPusat {
+        return Err("Only AdminPusat can change approval rules".into());
+    }
+    if max_amount.is_some_and(|max| max <= 0) {
+        return Err("Max amount must be positive".into());
+    }
+    match ctx.db.po_approval_rules().iter().find(|r| r.level == level && r.role == role) {
+        Some(rule) => {
+            ctx.db.po_approval_rules().id().update(PoApprovalRule { max_amount, ..rule });
+        }
+        None => {
+            ctx.db.po_approval_rules().insert(PoApprovalRule {
+                id: 0,
+                level,
+                role,
+                max_amount,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn remove_po_approval_rule(ctx: &ReducerContext, rule_id: u64) -> Result<(), String> {
+    let user = ctx.db.users().id().find(ctx.sender).ok_or("User not found")?;
+    if user.role != UserRole::AdminPusat {
+        return Err("Only AdminPusat can change approval rules".into());
+    }
+    if ctx.db.po_approval_rules().id().find(rule_id).is_none() {
+        return Err("Approval rule not found".into());
+    }
+    ctx.db.po_approval_rules().id().delete(rule_id);
+    Ok(())
+}
+
+// Records the caller's approval for the next pending level. The caller needs a role at that
+// level or above, cannot be the PO's creator and can approve each PO only once. The PO is
+// ordered once every required level is approved.
+#[reducer]
+pub fn approve_purchase_order(ctx: &ReducerContext, po_id: u64) -> Result<(), String> {
+    let mut po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if po.status != POStatus::Created {
+        return Err(format!("Purchase order is {:?}", po.status));
+    }
+    let user = ctx.db.users().id().find(ctx.sender).ok_or("User not found")?;
+    if po.created_by == ctx.sender {
+        return Err("You cannot approve a purchase order you created".into());
+    }
+    check_po_approver_scope(ctx, &user, &po)?;
+    let approvals: Vec<PoApproval> = ctx.db.po_approvals().poapproval_po_idx().filter(po_id).collect();
+    if approvals.iter().any(|a| a.approver == ctx.sender) {
+        return Err("You have already approved this purchase order".into());
+    }
+    let required = required_approval_levels(ctx, po.total);
+    let level = required.iter().copied().find(|l| !approvals.iter().any(|a| a.level == *l)).unwrap_or(0);
+    let allowed = required.is_empty()
+        || ctx.db.po_approval_rules().iter().any(|r| r.role == user.role && r.level >= level);
+    if !allowed {
+        return Err(format!("{:?} cannot approve level {} of this purchase order", user.role, level));
+    }
+    ctx.db.po_approvals().insert(PoApproval {
+        id: 0,
+        purchase_order_id: po_id,
+        level,
+        approver: ctx.sender,
+        role: user.role,
+        total: po.total,
+        approved_at: ctx.timestamp,
+    });
+    if required.last().is_none_or(|last| *last == level) {
+        po.status = approved_po_status(ctx, po_id);
+        ctx.db.purchase_orders().id().update(po);
+    }
+    Ok(())
+}
+
+// Cancels a PO before any goods arrive. Only AdminPusat and roles on the approval chain
+// may reject, within the same outlet/area scope as approving.
+#[reducer]
+pub fn reject_purchase_order(ctx: &ReducerContext, po_id: u64) -> Result<(), String> {
+    let mut po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if !matches!(po.status, POStatus::Created | POStatus::Ordered) {
+        return Err(format!("Cannot reject a purchase order that is {:?}", po.status));
+    }
+    let user = ctx.db.users().id().find(ctx.sender).ok_or("User not found")?;
+    if user.role != UserRole::AdminPusat && !ctx.db.po_approval_rules().iter().any(|r| r.role == user.role) {
+        return Err(format!("{:?} cannot reject purchase orders", user.role));
+    }
+    check_po_approver_scope(ctx, &user, &po)?;
+    if po_has_receipts(ctx, po_id) {
+        return Err("Purchase order has received goods; close it short instead".into());
+    }
+    po.status = POStatus::Cancelled;
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+// Records a goods received note against an ordered PO. Accepted quantities are posted to
+// the outlet's stock at the actual price; the PO stays open until every item is received.
+#[reducer]
+pub fn receive_purchase_order(
+    ctx: &ReducerContext,
+    po_id: u64,
+    lines: Vec<GoodsReceiptLineInput>,
+    notes: String,
+) -> Result<(), String> {
+    let mut po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if !matches!(po.status, POStatus::Ordered | POStatus::PartiallyReceived) {
+        return Err(format!("Cannot receive a purchase order that is {:?}", po.status));
+    }
+    if lines.is_empty() {
+        return Err("Goods received note has no lines".into());
+    }
+    let mut grn = ctx.db.goods_received_notes().insert(GoodsReceivedNote {
+        id: 0,
+        purchase_order_id: po_id,
+        outlet_id: po.outlet_id,
+        supplier_id: po.supplier_id,
+        total: 0,
+        notes,
+        received_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    let reference = format!("grn:{}", grn.id);
+    for line in lines {
+        let mut item =
+            ctx.db.purchase_order_items().id().find(line.po_item_id).ok_or("Purchase order item not found")?;
+        if item.purchase_order_id != po_id {
+            return Err(format!("Item {} is not on purchase order {}", item.id, po_id));
+        }
+        if line.received_quantity < 0 || line.rejected_quantity < 0 {
+            return Err("Quantities must not be negative".into());
+        }
+        if line.received_quantity == 0 && line.rejected_quantity == 0 {
+            return Err("Line has nothing received or rejected".into());
+        }
+        if line.rejected_quantity > 0 && line.rejection_reason.trim().is_empty() {
+            return Err("Rejected quantity needs a reason".into());
+        }
+        if line.price < 0 {
+            return Err("Price must not be negative".into());
+        }
+        let ing = ctx.db.ingredients().id().find(item.ingredient_id).ok_or("Ingredient not found")?;
+        let received = to_base_quantity(ctx, &ing, item.unit_id, line.received_quantity)?;
+        let rejected = to_base_quantity(ctx, &ing, item.unit_id, line.rejected_quantity)?;
+        if item.received_quantity + received > item.quantity {
+            return Err(format!("{} received exceeds the quantity ordered", ing.name));
+        }
+        let movement_id = if received > 0 {
+            let receipt = StockReceiptInput {
+                ingredient_id: item.ingredient_id,
+                unit_id: item.unit_id,
+                quantity: line.received_quantity,
+                lot_number: line.lot_number.clone(),
+                expires_at: line.expires_at,
+                price: line.price,
+            };
+            Some(post_receipt(ctx, po.outlet_id, &receipt, Some(po.supplier_id), &reference)?.id)
+        } else {
+            None
+        };
+        grn.total = grn.total.saturating_add(line.received_quantity.saturating_mul(line.price));
+        ctx.db.goods_received_lines().insert(GoodsReceivedLine {
+            id: 0,
+            grn_id: grn.id,
+            po_item_id: item.id,
+            ingredient_id: item.ingredient_id,
+            unit_id: item.unit_id,
+            received_quantity: received,
+            rejected_quantity: rejected,
+            rejection_reason: line.rejection_reason,
+            price: line.price,
+            movement_id,
+        });
+        item.received_quantity += received;
+        ctx.db.purchase_order_items().id().update(item);
+    }
+    ctx.db.goods_received_notes().id().update(grn);
+    let complete =
+        ctx.db.purchase_order_items().poi_po_idx().filter(po_id).all(|it| it.received_quantity >= it.quantity);
+    po.status = if complete { POStatus::Received } else { POStatus::PartiallyReceived };
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+// Stops waiting for the rest of a partially received PO.
+#[reducer]
+pub fn close_purchase_order_short(ctx: &ReducerContext, po_id: u64) -> Result<(), String> {
+    let mut po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if !matches!(po.status, POStatus::Ordered | POStatus::PartiallyReceived) {
+        return Err(format!("Cannot close a purchase order that is {:?}", po.status));
+    }
+    po.status = POStatus::ClosedShort;
+    ctx.db.purchase_orders().id().update(po);
+    Ok(())
+}
+
+// Purchase returns
+
+// Returns accepted goods from one goods received note. Stock leaves the lot it arrived in
+// and a debit note reduces the supplier's payable until the return is resolved.
+#[reducer]
+pub fn create_purchase_return(
+    ctx: &ReducerContext,
+    grn_id: u64,
+    lines: Vec<PurchaseReturnLineInput>,
+    notes: String,
+) -> Result<(), String> {
+    let grn = ctx.db.goods_received_notes().id().find(grn_id).ok_or("Goods received note not found")?;
+    let po = ctx.db.purchase_orders().id().find(grn.purchase_order_id).ok_or("Purchase order not found")?;
+    if !matches!(po.status, POStatus::PartiallyReceived | POStatus::Received | POStatus::ClosedShort) {
+        return Err(format!("Cannot return goods on a purchase order that is {:?}", po.status));
+    }
+    if lines.is_empty() {
+        return Err("Purchase return has no lines".into());
+    }
+    let mut ret = ctx.db.purchase_returns().insert(PurchaseReturn {
+        id: 0,
+        purchase_order_id: grn.purchase_order_id,
+        grn_id,
+        supplier_id: grn.supplier_id,
+        outlet_id: grn.outlet_id,
+        total: 0,
+        resolution: PurchaseReturnResolution::Pending,
+        notes,
+        created_by: ctx.sender,
+        date: ctx.timestamp,
+        resolved_at: None,
+    });
+    let reference = format!("return:{}", ret.id);
+    for line in lines {
+        let grn_line =
+            ctx.db.goods_received_lines().id().find(line.grn_line_id).ok_or("Goods received line not found")?;
+        if grn_line.grn_id != grn_id {
+            return Err(format!("Line {} is not on goods received note {}", grn_line.id, grn_id));
+        }
+        if line.quantity <= 0 {
+            return Err("Quantity must be positive".into());
+        }
+        if line.reason.trim().is_empty() {
+            return Err("Each returned line needs a reason".into());
+        }
+        let ing = ctx.db.ingredients().id().find(grn_line.ingredient_id).ok_or("Ingredient not found")?;
+        let quantity = to_base_quantity(ctx, &ing, grn_line.unit_id, line.quantity)?;
+        let already_returned: i64 = ctx
+            .db
+            .purchase_return_lines()
+            .preturnline_grnline_idx()
+            .filter(grn_line.id)
+            .map(|l| l.quantity)
+            .sum();
+        if already_returned + quantity > grn_line.received_quantity {
+            return Err(format!("{} returned exceeds the quantity accepted on this receipt", ing.name));
+        }
+        let st = require_outlet_stock(ctx, ing.id, grn.outlet_id)?;
+        let movement_type = StockMovementType::PurchaseReturn;
+        let movement = post_stock_movement(ctx, st.id, -quantity, movement_type, &reference, &line.reason, None)?;
+        // Take it out of the lot the receipt created; anything already used up there comes from FEFO.
+        let mut left = quantity;
+        let lot_id = grn_line
+            .movement_id
+            .and_then(|mid| ctx.db.lot_movements().lotmove_movement_idx().filter(mid).next())
+            .map(|lm| lm.lot_id);
+        if let Some(mut lot) = lot_id.and_then(|id| ctx.db.stock_lots().id().find(id)) {
+            let take = left.min(lot.quantity_remaining);
+            if take > 0 {
+                lot.quantity_remaining -= take;
+                if lot.quantity_remaining == 0 {
+                    lot.status = LotStatus::Depleted;
+                }
+                ctx.db.lot_movements().insert(LotMovement {
+                    id: 0,
+                    lot_id: lot.id,
+                    movement_id: movement.id,
+                    quantity: -take,
+                });
+                ctx.db.stock_lots().id().update(lot);
+                left -= take;
+            }
+        }
+        if left > 0 {
+            consume_lots_fefo(ctx, &movement, left)?;
+        }
+        ret.total = ret.total.saturating_add(line.quantity.saturating_mul(grn_line.price));
+        ctx.db.purchase_return_lines().insert(PurchaseReturnLine {
+            id: 0,
+            return_id: ret.id,
+            grn_line_id: grn_line.id,
+            ingredient_id: ing.id,
+            quantity,
+            price: grn_line.price,
+            movement_id: movement.id,
+            reason: line.reason,
+        });
+    }
+    let note = ctx.db.supplier_debit_notes().insert(SupplierDebitNote {
+        id: 0,
+        supplier_id: ret.supplier_id,
+        return_id: ret.id,
+        amount: ret.total,
+        invoice_id: None,
+        status: PurchaseReturnResolution::Pending,
+        date: ctx.timestamp,
+    });
+    let invoice = ctx
+        .db
+        .supplier_invoices()
+        .invoice_po_idx()
+        .filter(ret.purchase_order_id)
+        .find(|inv| invoice_owed(inv) >= note.amount);
+    if let Some(mut invoice) = invoice {
+        apply_debit_note(ctx, note, &mut invoice);
+        ctx.db.supplier_invoices().id().update(invoice);
+    }
+    ctx.db.purchase_returns().id().update(ret);
+    Ok(())
+}
+
+// Replaced reopens the returned quantities on the PO, even one closed short, so the
+// replacement is received through receive_purchase_order. The replacement is not invoiced
+// again: the original invoice covers it, so the debit note is voided and any credit it gave
+// that invoice is taken back. Credited confirms the note.
+#[reducer]
+pub fn resolve_purchase_return(
+    ctx: &ReducerContext,
+    return_id: u64,
+    resolution: PurchaseReturnResolution,
+) -> Result<(), String> {
+    let mut ret = ctx.db.purchase_returns().id().find(return_id).ok_or("Purchase return not found")?;
+    if ret.resolution != PurchaseReturnResolution::Pending {
+        return Err(format!("Purchase return is already {:?}", ret.resolution));
+    }
+    if resolution == PurchaseReturnResolution::Pending {
+        return Err("Resolution must be Replaced or Credited".into());
+    }
+    if resolution == PurchaseReturnResolution::Replaced {
+        for line in ctx.db.purchase_return_lines().preturnline_return_idx().filter(return_id) {
+            let Some(grn_line) = ctx.db.goods_received_lines().id().find(line.grn_line_id) else {
+                continue;
+            };
+            if let Some(mut item) = ctx.db.purchase_order_items().id().find(grn_line.po_item_id) {
+                item.received_quantity = (item.received_quantity - line.quantity).max(0);
+                ctx.db.purchase_order_items().id().update(item);
+            }
+        }
+        let mut po = ctx.db.purchase_orders().id().find(ret.purchase_order_id).ok_or("Purchase order not found")?;
+        if matches!(po.status, POStatus::Received | POStatus::ClosedShort) {
+            po.status = POStatus::PartiallyReceived;
+            ctx.db.purchase_orders().id().update(po);
+        }
+    }
+    let note = ctx
+        .db
+        .supplier_debit_notes()
+        .debitnote_supplier_idx()
+        .filter(ret.supplier_id)
+        .find(|n| n.return_id == return_id);
+    if let Some(mut note) = note {
+        if resolution == PurchaseReturnResolution::Replaced {
+            if let Some(mut invoice) = note.invoice_id.and_then(|id| ctx.db.supplier_invoices().id().find(id)) {
+                credit_invoice(ctx, &mut invoice, -note.amount);
+                ctx.db.supplier_invoices().id().update(invoice);
+            }
+        }
+        note.status = resolution.clone();
+        ctx.db.supplier_debit_notes().id().update(note);
+    }
+    ret.resolution = resolution;
+    ret.resolved_at = Some(ctx.timestamp);
+    ctx.db.purchase_returns().id().update(ret);
+    Ok(())
+}
+
+// Reordering
+
+fn is_on_open_po(ctx: &ReducerContext, outlet_id: u64, ingredient_id: u64) -> bool {
+    ctx.db
+        .purchase_orders()
+        .po_outlet_idx()
+        .filter(outlet_id)
+        .filter(|po| matches!(po.status, POStatus::Created | POStatus::Ordered | POStatus::PartiallyReceived))
+        .any(|po| {
+            ctx.db
+                .purchase_order_items()
+                .poi_po_idx()
+                .filter(po.id)
+                .any(|it| it.ingredient_id == ingredient_id)
+        })
+}
+
+// Creates one draft PO per outlet and preferred supplier for ingredients below par.
+// Ingredients already on an open PO are skipped. Returns the number of POs created.
+fn generate_reorders(ctx: &ReducerContext, outlet_id: Option<u64>) -> usize {
+    let rules: Vec<ReorderRule> = match outlet_id {
+        Some(oid) => ctx.db.reorder_rules().reorder_outlet_idx().filter(oid).collect(),
+        None => ctx.db.reorder_rules().iter().collect(),
+    };
+    let mut groups: Vec<((u64, u64), Vec<PurchaseOrderItem>)> = Vec::new();
+    for rule in rules {
+        let Some(ing) = ctx.db.ingredients().id().find(rule.ingredient_id) else {
+            continue;
+        };
+        let (stock, avg_cost) = outlet_stock(ctx, rule.ingredient_id, rule.outlet_id)
+            .map(|st| (st.stock, st.avg_cost))
+            .unwrap_or((0, 0));
+        if ing.status != IngredientStatus::Active || stock >= rule.par_level {
+            continue;
+        }
+        if is_on_open_po(ctx, rule.outlet_id, rule.ingredient_id) {
+            continue;
+        }
+        let Some(unit) = ctx.db.units_of_measure().id().find(rule.purchase_unit_id) else {
+            continue;
+        };
+        let units = if rule.reorder_qty > 0 {
+            rule.reorder_qty
+        } else {
+            // round the shortfall up to whole purchase units
+            (rule.par_level - stock + unit.factor - 1) / unit.factor
+        };
+        // catalog price, or estimated from the moving-average cost
+        let price = catalog_price(ctx, rule.preferred_supplier_id, ing.id, unit.id, ctx.timestamp)
+            .map(|p| p.price)
+            .unwrap_or_else(|| avg_cost.saturating_mul(unit.factor));
+        let item = PurchaseOrderItem {
+            id: 0,
+            purchase_order_id: 0,
+            ingredient_id: ing.id,
+            unit_id: unit.id,
+            ordered_quantity: units,
+            quantity: units.saturating_mul(unit.factor),
+            received_quantity: 0,
+            price,
+            catalog_price: None,
+            price_flagged: false,
+        };
+        let key = (rule.outlet_id, rule.preferred_supplier_id);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, items)) => items.push(item),
+            None => groups.push((key, vec![item])),
+        }
+    }
+    let created = groups.len();
+    for ((outlet_id, supplier_id), items) in groups {
+        let total = items.iter().map(po_line_total).sum();
+        let po = ctx.db.purchase_orders().insert(PurchaseOrder {
             id: 0,
-            name: format!("Karyawan {}", i + 1),
-            position: pos,
             outlet_id,
-            salary,
-            status: EmploymentStatus::Active,
+            supplier_id,
+            total,
+            status: POStatus::Created,
+            date: ctx.timestamp,
+            expected_delivery: expected_delivery(ctx, supplier_id, ctx.timestamp),
+            created_by: ctx.sender,
+            revision: 0,
         });
+        for it in items {
+            let mut it = PurchaseOrderItem {
+                purchase_order_id: po.id,
+                ..it
+            };
+            apply_catalog_price(ctx, &po, &mut it);
+            ctx.db.purchase_order_items().insert(it);
+        }
+        log::info!("Draft purchase order {} created for outlet {} from reorder rules", po.id, outlet_id);
     }
-    let employee_ids: Vec<u64> = ctx.db.employees().iter().map(|e| e.id).collect();
+    created
+}
 
-    // 6) Sales (30) with items
-    for i in 0..30 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let ts = safe_sub(now, days((i as i64 % 30) + 1)).checked_add(hours((i % 8) as i64)).unwrap_or(now);
-        let mut total = 0i64;
-        let sale = ctx.db.sales().insert(Sale {
+#[reducer]
+pub fn set_reorder_rule(
+    ctx: &ReducerContext,
+    ingredient_id: u64,
+    outlet_id: u64,
+    par_level: i64,
+    reorder_qty: i64,
+    purchase_unit_id: u64,
+    preferred_supplier_id: u64,
+) -> Result<(), String> {
+    let ing = ctx.db.ingredients().id().find(ingredient_id).ok_or("Ingredient not found")?;
+    if ctx.db.suppliers().id().find(preferred_supplier_id).is_none() {
+        return Err("Supplier not found".into());
+    }
+    if par_level < 0 || reorder_qty < 0 {
+        return Err("Par level and reorder quantity must not be negative".into());
+    }
+    // validates that the purchase unit fits the ingredient
+    to_base_quantity(ctx, &ing, purchase_unit_id, 1)?;
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    let existing = ctx
+        .db
+        .reorder_rules()
+        .reorder_ingredient_idx()
+        .filter(ingredient_id)
+        .find(|r| r.outlet_id == outlet_id);
+    if let Some(mut r) = existing {
+        r.par_level = par_level;
+        r.reorder_qty = reorder_qty;
+        r.purchase_unit_id = purchase_unit_id;
+        r.preferred_supplier_id = preferred_supplier_id;
+        ctx.db.reorder_rules().id().update(r);
+    } else {
+        ctx.db.reorder_rules().insert(ReorderRule {
             id: 0,
+            ingredient_id,
             outlet_id,
-            total: 0,
-            payment_method: match i % 4 {
-                0 => PaymentMethod::Cash,
-                1 => PaymentMethod::Card,
-                2 => PaymentMethod::EWallet,
-                _ => PaymentMethod::Transfer,
-            },
-            date: ts,
+            par_level,
+            reorder_qty,
+            purchase_unit_id,
+            preferred_supplier_id,
         });
-        let item_count: usize = 1 + (i as usize % 3);
-        for j in 0..item_count {
-            let prod_id = product_ids[(i as usize + j) % product_ids.len()];
-            if let Some(prod) = ctx.db.products().id().find(prod_id) {
-                let qty: i32 = 1 + (j as i32);
-                total = total.saturating_add(prod.price.saturating_mul(qty as i64));
-                ctx.db.sale_items().insert(SaleItem {
-                    id: 0,
-                    sale_id: sale.id,
-                    product_id: prod.id,
-                    quantity: qty,
-                    price: prod.price,
-                });
-            }
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn generate_reorder_suggestions(ctx: &ReducerContext, outlet_id: u64) -> Result<(), String> {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    generate_reorders(ctx, Some(outlet_id));
+    Ok(())
+}
+
+#[reducer]
+pub fn run_reorder_job(ctx: &ReducerContext, _schedule: ReorderSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("run_reorder_job may only be invoked by the scheduler".into());
+    }
+    let created = generate_reorders(ctx, None);
+    log::info!("Reorder job created {} draft purchase orders", created);
+    Ok(())
+}
+
+// Supplier scoring
+
+// Scorecards cover this many days of history.
+const SUPPLIER_SCORE_WINDOW_DAYS: u64 = 90;
+
+// Rate in basis points, or None without data.
+fn rate_bps(part: i64, whole: i64) -> Option<i64> {
+    if whole <= 0 {
+        return None;
+    }
+    Some(part.clamp(0, whole).saturating_mul(10_000) / whole)
+}
+
+fn score_supplier(ctx: &ReducerContext, supplier_id: u64, period_start: Timestamp) -> SupplierScore {
+    let in_period = |t: Timestamp| t > period_start && t <= ctx.timestamp;
+    let grns: Vec<GoodsReceivedNote> = ctx
+        .db
+        .goods_received_notes()
+        .iter()
+        .filter(|g| g.supplier_id == supplier_id && in_period(g.date))
+        .collect();
+    let mut on_time = 0i64;
+    let (mut accepted, mut rejected) = (0i64, 0i64);
+    for grn in grns.iter() {
+        let expected = ctx.db.purchase_orders().id().find(grn.purchase_order_id).map(|po| po.expected_delivery);
+        if expected.is_some_and(|e| grn.date <= e) {
+            on_time += 1;
         }
-        if let Some(mut s) = ctx.db.sales().id().find(sale.id) {
-            s.total = total;
-            ctx.db.sales().id().update(s);
+        for line in ctx.db.goods_received_lines().grnline_grn_idx().filter(grn.id) {
+            accepted += line.received_quantity;
+            rejected += line.rejected_quantity;
+        }
+    }
+    let (mut received, mut ordered) = (0i64, 0i64);
+    let closed = ctx.db.purchase_orders().iter().filter(|po| {
+        po.supplier_id == supplier_id
+            && matches!(po.status, POStatus::Received | POStatus::ClosedShort)
+            && in_period(po.expected_delivery)
+    });
+    for po in closed {
+        for it in ctx.db.purchase_order_items().poi_po_idx().filter(po.id) {
+            received += it.received_quantity.min(it.quantity);
+            ordered += it.quantity;
         }
     }
+    // Average size of the price changes that took effect in the period.
+    let prices: Vec<SupplierPrice> = ctx.db.supplier_prices().supprice_supplier_idx().filter(supplier_id).collect();
+    let changes: Vec<i64> = prices
+        .iter()
+        .filter(|p| in_period(p.effective_from))
+        .filter_map(|p| {
+            let prev = prices.iter().find(|q| {
+                q.ingredient_id == p.ingredient_id
+                    && q.unit_id == p.unit_id
+                    && q.effective_to == Some(p.effective_from)
+            })?;
+            rate_bps((p.price - prev.price).abs(), prev.price)
+        })
+        .collect();
+    let price_stability = if changes.is_empty() {
+        None
+    } else {
+        Some(10_000 - changes.iter().sum::<i64>() / changes.len() as i64)
+    };
+    let on_time_rate = rate_bps(on_time, grns.len() as i64);
+    let fill_rate = rate_bps(received, ordered);
+    let quality_rate = rate_bps(accepted, accepted + rejected);
+    let returned: i64 = ctx
+        .db
+        .purchase_returns()
+        .preturn_supplier_idx()
+        .filter(supplier_id)
+        .filter(|r| in_period(r.date))
+        .flat_map(|r| ctx.db.purchase_return_lines().preturnline_return_idx().filter(r.id))
+        .map(|l| l.quantity)
+        .sum();
+    let return_rate = rate_bps(returned, accepted);
+    let weighted = [
+        (on_time_rate, 25),
+        (fill_rate, 25),
+        (quality_rate, 20),
+        (return_rate.map(|r| 10_000 - r), 15),
+        (price_stability, 15),
+    ];
+    let weight: i64 = weighted.iter().filter(|(r, _)| r.is_some()).map(|(_, w)| w).sum();
+    let score = if weight == 0 {
+        None
+    } else {
+        Some(weighted.iter().filter_map(|(r, w)| r.map(|r| r * w)).sum::<i64>() / weight)
+    };
+    SupplierScore {
+        id: 0,
+        supplier_id,
+        period_start,
+        period_end: ctx.timestamp,
+        deliveries: grns.len() as u32,
+        on_time_rate,
+        fill_rate,
+        quality_rate,
+        return_rate,
+        price_stability,
+        score,
+        // 0% maps to 1 star, 100% to 5
+        rating: score.map(|s| 1 + ((s * 4 + 5_000) / 10_000) as i32).unwrap_or(0),
+        computed_at: ctx.timestamp,
+    }
+}
 
-    // 7) Purchase Orders (15) with items
-    for i in 0..15 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let supplier_id = supplier_ids[i as usize % supplier_ids.len()];
-        let status = match i % 4 {
-            0 => POStatus::Created,
-            1 => POStatus::Ordered,
-            2 => POStatus::Received,
-            _ => POStatus::Cancelled,
-        };
-        let total = (500_000i64 + ((i % 10) as i64) * 75_000) * 100;
-        let po = ctx.db.purchase_orders().insert(PurchaseOrder {
-            id: 0,
-            outlet_id,
-            supplier_id,
-            total,
-            status,
-            date: safe_sub(now, days(40 - (i as i64 % 30))),
-        });
-        // Add 1-3 items from ingredients
-        let item_count = 1 + (i % 3);
-        for j in 0..item_count {
-            let ing_id = ingredient_ids[(i as usize + j as usize) % ingredient_ids.len()];
-            let qty = 5 + (j as i64) * 3;
-            let price = 25_000i64 * 100 + (j as i64) * 5_000 * 100;
-            ctx.db.purchase_order_items().insert(PurchaseOrderItem {
-                id: 0,
-                purchase_order_id: po.id,
-                ingredient_id: ing_id,
-                quantity: qty,
-                price,
-            });
+// Adds a scorecard for every supplier and updates Supplier.rating from it. Suppliers
+// without any data in the window keep their current rating.
+fn score_all_suppliers(ctx: &ReducerContext) {
+    let window = TimeDuration::from_duration(Duration::from_secs(SUPPLIER_SCORE_WINDOW_DAYS * 86400));
+    let period_start = ctx.timestamp.checked_sub(window).unwrap_or(Timestamp::UNIX_EPOCH);
+    let suppliers: Vec<Supplier> = ctx.db.suppliers().iter().collect();
+    for mut supplier in suppliers {
+        let card = ctx.db.supplier_scores().insert(score_supplier(ctx, supplier.id, period_start));
+        if card.score.is_some() && supplier.rating != card.rating {
+            supplier.rating = card.rating;
+            ctx.db.suppliers().id().update(supplier);
         }
     }
+}
 
-    // 8) Distributions (10)
-    for i in 0..10 {
-        let from_outlet_id = outlet_ids[i as usize % outlet_count];
-        let to_outlet_id = outlet_ids[(i as usize + 1) % outlet_count];
-        let ingredient_id = ingredient_ids[i as usize % ingredient_ids.len()];
-        let quantity = 5 + (i as i64 % 10);
-        let status = match i % 3 {
-            0 => DistributionStatus::Pending,
-            1 => DistributionStatus::InTransit,
-            _ => DistributionStatus::Delivered,
-        };
-        ctx.db.distributions().insert(Distribution {
-            id: 0,
-            from_outlet_id,
-            to_outlet_id,
-            ingredient_id,
-            quantity,
-            status,
-            date: safe_sub(now, days(20 - (i as i64 % 15))),
-        });
-    }
+#[reducer]
+pub fn recompute_supplier_scores(ctx: &ReducerContext) -> Result<(), String> {
+    score_all_suppliers(ctx);
+    Ok(())
+}
 
-    // 9) Daily Checklists (18) one per outlet
-    for (i, oid) in outlet_ids.iter().enumerate() {
-        ctx.db.daily_checklists().insert(DailyChecklist {
-            id: 0,
-            outlet_id: *oid,
-            checklist_name: "Buka toko, cek kebersihan, stok bahan, mesin kasir".to_string(),
-            is_completed: (i % 2 == 0),
-            date: safe_sub(now, days((i as i64 % 10))),
-        });
+#[reducer]
+pub fn run_supplier_scoring(ctx: &ReducerContext, _schedule: SupplierScoreSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("run_supplier_scoring may only be invoked by the scheduler".into());
     }
+    score_all_suppliers(ctx);
+    Ok(())
+}
 
-    // 10) Shift Reports (10)
-    for i in 0..10 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let employee_id = employee_ids[i as usize % employee_ids.len()];
-        let shift_start = safe_sub(now, days((i as i64 % 20)));
-        let shift_end = safe_add(shift_start, hours(8));
-        let initial_cash = (1_000_000i64 + (i as i64) * 100_000) * 100;
-        let final_cash = initial_cash.saturating_add(500_000i64 * 100);
-        let status = if i % 3 == 0 { ShiftStatus::Open } else { ShiftStatus::Closed };
-        ctx.db.shift_reports().insert(ShiftReport {
-            id: 0,
-            outlet_id,
-            employee_id,
-            shift_start,
-            shift_end,
-            initial_cash,
-            final_cash: if status == ShiftStatus::Open { 0 } else { final_cash },
-            status,
+// Accounts payable
+
+fn add_payable(ctx: &ReducerContext, supplier_id: u64, amount: i64, invoices: i32) {
+    if let Some(mut payable) = ctx.db.supplier_payables().supplier_id().find(supplier_id) {
+        payable.outstanding = payable.outstanding.saturating_add(amount);
+        payable.open_invoices = payable.open_invoices.saturating_add_signed(invoices);
+        ctx.db.supplier_payables().supplier_id().update(payable);
+    } else {
+        ctx.db.supplier_payables().insert(SupplierPayable {
+            supplier_id,
+            outstanding: amount,
+            open_invoices: invoices.max(0) as u32,
         });
     }
+}
 
-    // 11) Candidates (15)
-    for i in 0..15 {
-        let st = match i % 4 {
-            0 => CandidateStatus::Applied,
-            1 => CandidateStatus::Interview,
-            2 => CandidateStatus::Hired,
-            _ => CandidateStatus::Rejected,
-        };
-        ctx.db.candidates().insert(Candidate {
-            id: 0,
-            name: format!("Pelamar {}", i + 1),
-            position: positions[i as usize % positions.len()].to_string(),
-            phone: format!("08{:09}", 111111111 + i),
-            email: format!("pelamar{}@mail.com", i + 1),
-            status: st,
-        });
+fn invoice_owed(invoice: &SupplierInvoice) -> i64 {
+    invoice.total - invoice.credited - invoice.amount_paid
+}
+
+// Adds a debit note's credit to the invoice, or takes it back when the amount is negative,
+// keeping the payment status and the supplier's payable in step.
+fn credit_invoice(ctx: &ReducerContext, invoice: &mut SupplierInvoice, amount: i64) {
+    let was_open = invoice_owed(invoice) > 0;
+    invoice.credited += amount;
+    let open = invoice_owed(invoice) > 0;
+    invoice.payment_status = if !open {
+        InvoicePaymentStatus::Paid
+    } else if invoice.amount_paid > 0 || invoice.credited > 0 {
+        InvoicePaymentStatus::PartiallyPaid
+    } else {
+        InvoicePaymentStatus::Unpaid
+    };
+    add_payable(ctx, invoice.supplier_id, -amount, open as i32 - was_open as i32);
+}
+
+// Applies a pending debit note to an invoice that still owes at least its amount.
+// The caller saves the invoice.
+fn apply_debit_note(ctx: &ReducerContext, mut note: SupplierDebitNote, invoice: &mut SupplierInvoice) {
+    if note.invoice_id.is_some() || invoice_owed(invoice) < note.amount {
+        return;
     }
+    credit_invoice(ctx, invoice, note.amount);
+    note.invoice_id = Some(invoice.id);
+    ctx.db.supplier_debit_notes().id().update(note);
+}
 
-    // 12) Promotions (5)
-    for i in 0..5 {
-        let (start_date, end_date) = if i == 0 {
-            (safe_sub(now, days(5)), safe_add(now, days(5)))
-        } else if i == 1 {
-            (safe_add(now, days(5)), safe_add(now, days(15)))
-        } else {
-            let start = safe_sub(now, days(20 + (i as i64 * 3)));
-            let end = safe_sub(now, days(10 + (i as i64 * 2)));
-            (start, end)
-        };
-        ctx.db.promotions().insert(Promotion {
+// Returned quantity of a PO item whose debit note has not been applied to an invoice yet.
+// Replaced returns are already taken off the item's received quantity.
+fn uncredited_return_quantity(ctx: &ReducerContext, po_item_id: u64) -> i64 {
+    ctx.db
+        .goods_received_lines()
+        .grnline_poitem_idx()
+        .filter(po_item_id)
+        .flat_map(|g| ctx.db.purchase_return_lines().preturnline_grnline_idx().filter(g.id))
+        .filter(|l| {
+            let Some(ret) = ctx.db.purchase_returns().id().find(l.return_id) else {
+                return false;
+            };
+            ret.resolution != PurchaseReturnResolution::Replaced
+                && ctx
+                    .db
+                    .supplier_debit_notes()
+                    .debitnote_supplier_idx()
+                    .filter(ret.supplier_id)
+                    .any(|n| n.return_id == ret.id && n.invoice_id.is_none())
+        })
+        .map(|l| l.quantity)
+        .sum()
+}
+
+// Records a supplier invoice against a PO and runs the three-way match: every line must
+// carry the PO price and the price the goods were received at, and must not exceed what
+// was received, less returns, and not yet invoiced; the stated total must equal the sum
+// of the lines. Mismatches are recorded, not rejected. An invoice that bills goods returned
+// before it arrived takes their pending debit notes as credit.
+#[reducer]
+pub fn record_supplier_invoice(
+    ctx: &ReducerContext,
+    po_id: u64,
+    invoice_number: String,
+    invoice_date: Timestamp,
+    total: i64,
+    lines: Vec<SupplierInvoiceLineInput>,
+) -> Result<(), String> {
+    let po = ctx.db.purchase_orders().id().find(po_id).ok_or("Purchase order not found")?;
+    if matches!(po.status, POStatus::Created | POStatus::Cancelled) {
+        return Err(format!("Cannot invoice a purchase order that is {:?}", po.status));
+    }
+    let supplier = ctx.db.suppliers().id().find(po.supplier_id).ok_or("Supplier not found")?;
+    if invoice_number.trim().is_empty() {
+        return Err("Invoice number is required".into());
+    }
+    if ctx
+        .db
+        .supplier_invoices()
+        .invoice_supplier_idx()
+        .filter(supplier.id)
+        .any(|inv| inv.invoice_number == invoice_number)
+    {
+        return Err(format!("Invoice {} from {} is already recorded", invoice_number, supplier.name));
+    }
+    if lines.is_empty() {
+        return Err("Invoice has no lines".into());
+    }
+    if total < 0 {
+        return Err("Total must not be negative".into());
+    }
+    let terms = TimeDuration::from_duration(Duration::from_secs(supplier.payment_terms_days as u64 * 86400));
+    let mut invoice = ctx.db.supplier_invoices().insert(SupplierInvoice {
+        id: 0,
+        supplier_id: supplier.id,
+        purchase_order_id: po_id,
+        invoice_number,
+        invoice_date,
+        due_date: invoice_date.checked_add(terms).unwrap_or(invoice_date),
+        total,
+        credited: 0,
+        amount_paid: 0,
+        match_status: InvoiceMatchStatus::Matched,
+        match_notes: String::new(),
+        payment_status: InvoicePaymentStatus::Unpaid,
+        recorded_by: ctx.sender,
+        recorded_at: ctx.timestamp,
+    });
+    let mut checked = Vec::with_capacity(lines.len());
+    for line in lines {
+        let item = ctx.db.purchase_order_items().id().find(line.po_item_id).ok_or("Purchase order item not found")?;
+        if item.purchase_order_id != po_id {
+            return Err(format!("Item {} is not on purchase order {}", item.id, po_id));
+        }
+        if line.quantity <= 0 || line.price < 0 {
+            return Err("Invoice lines need a positive quantity and a non-negative price".into());
+        }
+        let ing = ctx.db.ingredients().id().find(item.ingredient_id).ok_or("Ingredient not found")?;
+        let quantity = to_base_quantity(ctx, &ing, item.unit_id, line.quantity)?;
+        let invoiced_elsewhere: i64 = ctx
+            .db
+            .supplier_invoices()
+            .invoice_po_idx()
+            .filter(po_id)
+            .filter(|inv| inv.id != invoice.id)
+            .flat_map(|inv| ctx.db.supplier_invoice_lines().invline_invoice_idx().filter(inv.id))
+            .filter(|l| l.po_item_id == item.id)
+            .map(|l| l.quantity)
+            .sum();
+        let received_price = ctx
+            .db
+            .goods_received_lines()
+            .grnline_poitem_idx()
+            .filter(item.id)
+            .filter(|g| g.received_quantity > 0)
+            .max_by_key(|g| g.id)
+            .map(|g| g.price);
+        let returned = uncredited_return_quantity(ctx, item.id);
+        let billable = item.received_quantity - invoiced_elsewhere;
+        checked.push((item, ing, quantity, line, received_price, billable, returned));
+    }
+    // Returned goods are billable only when this invoice bills them; their debit notes then offset it.
+    let bills_returns = checked.iter().any(|(_, _, quantity, _, _, billable, returned)| {
+        *returned > 0 && *quantity > billable - returned
+    });
+    let mut problems: Vec<String> = Vec::new();
+    let mut line_sum = 0i64;
+    for (item, ing, quantity, line, received_price, billable, returned) in checked {
+        let received = if bills_returns { billable } else { billable - returned };
+        let mut matched = true;
+        if line.price != item.price {
+            matched = false;
+            problems.push(format!("{}: invoiced at {}, ordered at {}", ing.name, line.price, item.price));
+        }
+        if let Some(price) = received_price.filter(|p| *p != line.price) {
+            matched = false;
+            problems.push(format!("{}: invoiced at {}, received at {}", ing.name, line.price, price));
+        }
+        if quantity > received {
+            matched = false;
+            problems.push(format!("{}: invoiced {}, received and not yet invoiced {}", ing.name, quantity, received));
+        }
+        line_sum = line_sum.saturating_add(line.quantity.saturating_mul(line.price));
+        ctx.db.supplier_invoice_lines().insert(SupplierInvoiceLine {
             id: 0,
-            name: format!("Promo Spesial {}", i + 1),
-            discount_type: if i % 2 == 0 { DiscountType::Percentage } else { DiscountType::FixedAmount },
-            discount_value: if i % 2 == 0 { 1000 } else { 10_000 * 100 }, // 10% or Rp10.000
-            start_date,
-            end_date,
-            status: if i == 0 { PromotionStatus::Active } else { PromotionStatus::Ended },
+            invoice_id: invoice.id,
+            po_item_id: item.id,
+            ingredient_id: item.ingredient_id,
+            quantity,
+            price: line.price,
+            po_price: item.price,
+            received_price,
+            received_quantity: received,
+            matched,
         });
     }
+    if line_sum != total {
+        problems.push(format!("Invoice total {} does not equal its lines {}", total, line_sum));
+    }
+    if !problems.is_empty() {
+        log::warn!("Invoice {} failed three-way match: {}", invoice.invoice_number, problems.join("; "));
+        invoice.match_status = InvoiceMatchStatus::Mismatch;
+        invoice.match_notes = problems.join("; ");
+    }
+    if total == 0 {
+        invoice.payment_status = InvoicePaymentStatus::Paid;
+    }
+    add_payable(ctx, supplier.id, total, if total > 0 { 1 } else { 0 });
+    if bills_returns {
+        let notes: Vec<SupplierDebitNote> = ctx
+            .db
+            .supplier_debit_notes()
+            .debitnote_supplier_idx()
+            .filter(supplier.id)
+            .filter(|n| n.invoice_id.is_none() && n.status != PurchaseReturnResolution::Replaced)
+            .filter(|n| ctx.db.purchase_returns().id().find(n.return_id).is_some_and(|r| r.purchase_order_id == po_id))
+            .collect();
+        for note in notes {
+            apply_debit_note(ctx, note, &mut invoice);
+        }
+    }
+    ctx.db.supplier_invoices().id().update(invoice);
+    Ok(())
+}
 
-    // 13) Assets (30)
-    let asset_names = ["Kompor", "Kulkas", "AC", "Meja", "Kursi", "Wastafel"];
-    let asset_cats = ["Dapur", "Pendingin", "HVAC", "Furnitur", "Furnitur", "Sanitasi"];
-    for i in 0..30 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let idx = i as usize % asset_names.len();
-        let last_maint = safe_sub(now, days(15 - (i as i64 % 10)));
-        let status = match i % 5 {
-            1 => AssetStatus::Maintenance,
-            2 => AssetStatus::Broken,
-            _ => AssetStatus::InUse,
-        };
-        ctx.db.assets().insert(Asset {
-            id: 0,
-            outlet_id,
-            name: asset_names[idx].to_string(),
-            category: asset_cats[idx].to_string(),
-            status,
-            last_maintenance: last_maint,
-        });
+// Finance accepts a mismatched invoice for payment.
+#[reducer]
+pub fn override_invoice_mismatch(ctx: &ReducerContext, invoice_id: u64, note: String) -> Result<(), String> {
+    let mut invoice = ctx.db.supplier_invoices().id().find(invoice_id).ok_or("Invoice not found")?;
+    if invoice.match_status != InvoiceMatchStatus::Mismatch {
+        return Err("Invoice is not in mismatch".into());
+    }
+    if note.trim().is_empty() {
+        return Err("An override needs a note".into());
     }
+    invoice.match_status = InvoiceMatchStatus::Overridden;
+    invoice.match_notes = format!("{} | override: {}", invoice.match_notes, note);
+    ctx.db.supplier_invoices().id().update(invoice);
+    Ok(())
+}
 
-    // 14) CashFlow (50)
-    let categories = ["Sales", "Purchase", "Salary", "Rent", "Utilities"];
-    for i in 0..50 {
-        let outlet_id = outlet_ids[i as usize % outlet_count];
-        let ctype = if i % 2 == 0 { CashFlowType::Inflow } else { CashFlowType::Outflow };
-        let amount = (200_000i64 + ((i % 10) as i64) * 50_000) * 100;
-        ctx.db.cash_flow().insert(CashFlow {
-            id: 0,
-            outlet_id,
-            ctype,
-            category: categories[i as usize % categories.len()].to_string(),
-            amount,
-            date: safe_sub(now, days((i as i64 % 60))),
-            description: "Catatan kas harian".to_string(),
-        });
+// Pays all or part of a matched invoice and posts the payment to cash_flow at the PO's outlet.
+#[reducer]
+pub fn pay_supplier_invoice(
+    ctx: &ReducerContext,
+    invoice_id: u64,
+    amount: i64,
+    method: PaymentMethod,
+) -> Result<(), String> {
+    let mut invoice = ctx.db.supplier_invoices().id().find(invoice_id).ok_or("Invoice not found")?;
+    if invoice.match_status == InvoiceMatchStatus::Mismatch {
+        return Err("Invoice failed three-way match; resolve or override it first".into());
+    }
+    if amount <= 0 {
+        return Err("Amount must be positive".into());
     }
+    if amount > invoice_owed(&invoice) {
+        return Err(format!("Amount exceeds the {} still owed", invoice_owed(&invoice)));
+    }
+    let po = ctx.db.purchase_orders().id().find(invoice.purchase_order_id).ok_or("Purchase order not found")?;
+    let cash = ctx.db.cash_flow().insert(CashFlow {
+        id: 0,
+        outlet_id: po.outlet_id,
+        ctype: CashFlowType::Outflow,
+        category: "Purchase".to_string(),
+        amount,
+        date: ctx.timestamp,
+        description: format!("Pembayaran invoice {}", invoice.invoice_number),
+    });
+    ctx.db.supplier_payments().insert(SupplierPayment {
+        id: 0,
+        invoice_id,
+        supplier_id: invoice.supplier_id,
+        amount,
+        method,
+        cash_flow_id: cash.id,
+        paid_by: ctx.sender,
+        date: ctx.timestamp,
+    });
+    invoice.amount_paid += amount;
+    let settled = invoice_owed(&invoice) == 0;
+    invoice.payment_status = if settled { InvoicePaymentStatus::Paid } else { InvoicePaymentStatus::PartiallyPaid };
+    add_payable(ctx, invoice.supplier_id, -amount, if settled { -1 } else { 0 });
+    ctx.db.supplier_invoices().id().update(invoice);
+    Ok(())
+}
 
-    // 15) Users (seed 1 Admin - no conditional checks)
-    ctx.db.users().insert(User {
-        id: ctx.identity(),
-        username: "admin_pusat".to_string(),
-        role: UserRole::AdminPusat,
-        outlet_id: None,
+// Distribution
+
+#[reducer]
+pub fn request_distribution(
+    ctx: &ReducerContext,
+    from_outlet_id: u64,
+    to_outlet_id: u64,
+    ingredient_id: u64,
+    quantity: i64,
+    date: Timestamp,
+) -> Result<(), String> {
+    if from_outlet_id == to_outlet_id {
+        return Err("from_outlet_id and to_outlet_id must differ".into());
+    }
+    if quantity <= 0 {
+        return Err("Quantity must be positive".into());
+    }
+    ctx.db.distributions().insert(Distribution {
+        id: 0,
+        from_outlet_id,
+        to_outlet_id,
+        ingredient_id,
+        quantity,
+        status: DistributionStatus::Pending,
+        date,
     });
+    Ok(())
+}
 
+#[reducer]
+pub fn mark_distribution_delivered(ctx: &ReducerContext, distribution_id: u64) -> Result<(), String> {
+    let mut d = ctx.db.distributions().id().find(distribution_id).ok_or("Distribution not found")?;
+    if d.status == DistributionStatus::Delivered {
+        return Err("Distribution already delivered".into());
+    }
+    let reference = format!("distribution:{}", d.id);
+    let from = require_outlet_stock(ctx, d.ingredient_id, d.from_outlet_id)?;
+    let to = ensure_outlet_stock(ctx, d.ingredient_id, d.to_outlet_id)?;
+    let out = post_stock_movement(ctx, from.id, -d.quantity, StockMovementType::TransferOut, &reference, "", None)?;
+    let inbound =
+        post_stock_movement(ctx, to.id, d.quantity, StockMovementType::TransferIn, &reference, "", Some(out.unit_cost))?;
+    // Lots travel with the goods so a bad batch can be traced to every outlet that received it.
+    let drawn: Vec<LotMovement> = ctx.db.lot_movements().lotmove_movement_idx().filter(out.id).collect();
+    for lm in drawn {
+        let Some(src) = ctx.db.stock_lots().id().find(lm.lot_id) else {
+            continue;
+        };
+        let part = StockMovement {
+            delta: -lm.quantity,
+            unit_cost: src.unit_cost,
+            ..inbound.clone()
+        };
+        create_lot(ctx, &part, src.lot_number, src.supplier_id, Some(src.id), src.expires_at);
+    }
+    d.status = DistributionStatus::Delivered;
+    ctx.db.distributions().id().update(d);
     Ok(())
 }
 
-// Outlet management
+// Operations
 
 #[reducer]
-pub fn create_outlet(
-    ctx: &ReducerContext,
-    name: String,
-    area: String,
-    address: String,
+pub fn create_daily_checklist(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    checklist_name: String,
+    date: Timestamp,
 ) -> Result<(), String> {
-    ctx.db.outlets().insert(Outlet {
+    ctx.db.daily_checklists().insert(DailyChecklist {
         id: 0,
-        name,
-        area,
-        address,
-        status: OutletStatus::Open,
+        outlet_id,
+        checklist_name,
+        is_completed: false,
+        date,
     });
     Ok(())
 }
 
 #[reducer]
-pub fn update_outlet(
+pub fn update_checklist_status(
     ctx: &ReducerContext,
-    id: u64,
-    name: String,
-    area: String,
-    address: String,
-    status: OutletStatus,
+    checklist_id: u64,
+    is_completed: bool,
 ) -> Result<(), String> {
-    if let Some(mut o) = ctx.db.outlets().id().find(id) {
-        o.name = name;
-        o.area = area;
-        o.address = address;
-        o.status = status;
-        ctx.db.outlets().id().update(o);
+    if let Some(mut c) = ctx.db.daily_checklists().id().find(checklist_id) {
+        c.is_completed = is_completed;
+        ctx.db.daily_checklists().id().update(c);
         Ok(())
     } else {
-        Err("Outlet not found".into())
+        Err("Checklist not found".into())
     }
 }
 
 #[reducer]
-pub fn delete_outlet(ctx: &ReducerContext, id: u64) -> Result<(), String> {
-    ctx.db.outlets().id().delete(id);
+pub fn open_shift(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    employee_id: u64,
+    shift_start: Timestamp,
+    initial_cash: i64,
+) -> Result<(), String> {
+    ctx.db.shift_reports().insert(ShiftReport {
+        id: 0,
+        outlet_id,
+        employee_id,
+        shift_start,
+        shift_end: shift_start,
+        initial_cash,
+        final_cash: 0,
+        status: ShiftStatus::Open,
+    });
     Ok(())
 }
 
-// Employee management
+// HR
 
 #[reducer]
-pub fn create_employee(
+pub fn add_candidate(
     ctx: &ReducerContext,
     name: String,
     position: String,
-    outlet_id: u64,
-    salary: i64,
-    status: EmploymentStatus,
+    phone: String,
+    email: String,
 ) -> Result<(), String> {
-    ctx.db.employees().insert(Employee {
+    ctx.db.candidates().insert(Candidate {
         id: 0,
         name,
         position,
-        outlet_id,
-        salary,
-        status,
+        phone,
+        email,
+        status: CandidateStatus::Applied,
     });
     Ok(())
 }
 
 #[reducer]
-pub fn update_employee_status(
+pub fn update_candidate_status(
     ctx: &ReducerContext,
-    employee_id: u64,
-    status: EmploymentStatus,
+    candidate_id: u64,
+    status: CandidateStatus,
 ) -> Result<(), String> {
-    if let Some(mut e) = ctx.db.employees().id().find(employee_id) {
-        e.status = status.clone();
-        ctx.db.employees().id().update(e);
+    if let Some(mut c) = ctx.db.candidates().id().find(candidate_id) {
+        c.status = status.clone();
+        ctx.db.candidates().id().update(c);
         Ok(())
     } else {
-        Err("Employee not found".into())
+        Err("Candidate not found".into())
     }
 }
 
-// Inventory
+// Promotions
 
 #[reducer]
-pub fn add_product(
+pub fn create_promotion(
     ctx: &ReducerContext,
     name: String,
-    category: String,
-    price: i64,
-    outlet_id: u64,
+    discount_type: DiscountType,
+    discount_value: i64,
+    start_date: Timestamp,
+    end_date: Timestamp,
+    status: PromotionStatus,
 ) -> Result<(), String> {
-    ctx.db.products().insert(Product {
+    ctx.db.promotions().insert(Promotion {
         id: 0,
         name,
-        category,
-        price,
-        outlet_id,
+        discount_type,
+        discount_value,
+        start_date,
+        end_date,
+        status,
     });
     Ok(())
 }
 
+// Assets
+
 #[reducer]
-pub fn add_ingredient(
+pub fn add_asset(
     ctx: &ReducerContext,
-    name: String,
-    unit: String,
-    min_stock: i64,
-    stock: i64,
     outlet_id: u64,
-    status: IngredientStatus,
+    name: String,
+    category: String,
+    last_maintenance: Timestamp,
 ) -> Result<(), String> {
-    ctx.db.ingredients().insert(Ingredient {
+    ctx.db.assets().insert(Asset {
         id: 0,
-        name,
-        unit,
-        min_stock,
-        stock,
         outlet_id,
-        status,
+        name,
+        category,
+        status: AssetStatus::InUse,
+        last_maintenance,
     });
     Ok(())
 }
 
 #[reducer]
-pub fn update_inventory(
+pub fn update_asset_status(
     ctx: &ReducerContext,
-    ingredient_id: u64,
-    new_stock: i64,
+    asset_id: u64,
+    status: AssetStatus,
+    last_maintenance: Timestamp,
 ) -> Result<(), String> {
-    if let Some(mut ing) = ctx.db.ingredients().id().find(ingredient_id) {
-        ing.stock = new_stock;
-        ctx.db.ingredients().id().update(ing);
+    if let Some(mut a) = ctx.db.assets().id().find(asset_id) {
+        a.status = status.clone();
+        a.last_maintenance = last_maintenance;
+        ctx.db.assets().id().update(a);
         Ok(())
     } else {
-        Err("Ingredient not found".into())
+        Err("Asset not found".into())
     }
 }
 
-// Purchasing
+// Temperature logging
+
+// Minutes after a reading is due before it counts as missed.
+const TEMPERATURE_GRACE_MINUTES: u64 = 30;
+
+// Asset category of chillers and freezers.
+const COLD_STORAGE_CATEGORY: &str = "Pendingin";
+
+fn open_temperature_alert(
+    ctx: &ReducerContext,
+    asset_id: u64,
+    kind: &TemperatureAlertKind,
+) -> Option<TemperatureAlert> {
+    ctx.db
+        .temperature_alerts()
+        .tempalert_asset_idx()
+        .filter(asset_id)
+        .find(|a| a.kind == *kind && a.resolved_at.is_none())
+}
+
+fn raise_temperature_alert(
+    ctx: &ReducerContext,
+    monitor: &TemperatureMonitor,
+    kind: TemperatureAlertKind,
+    temperature: Option<i32>,
+) {
+    if let Some(mut a) = open_temperature_alert(ctx, monitor.asset_id, &kind) {
+        a.temperature = temperature.or(a.temperature);
+        ctx.db.temperature_alerts().id().update(a);
+        return;
+    }
+    ctx.db.temperature_alerts().insert(TemperatureAlert {
+        id: 0,
+        asset_id: monitor.asset_id,
+        outlet_id: monitor.outlet_id,
+        kind,
+        temperature,
+        acknowledged: false,
+        acknowledged_by: None,
+        created_at: ctx.timestamp,
+        resolved_at: None,
+    });
+}
+
+fn resolve_temperature_alert(ctx: &ReducerContext, asset_id: u64, kind: TemperatureAlertKind) {
+    if let Some(mut a) = open_temperature_alert(ctx, asset_id, &kind) {
+        a.resolved_at = Some(ctx.timestamp);
+        ctx.db.temperature_alerts().id().update(a);
+    }
+}
 
+// Creates or updates the acceptable range and reading interval for a cold storage asset.
 #[reducer]
-pub fn add_supplier(
+pub fn set_temperature_range(
     ctx: &ReducerContext,
-    name: String,
-    contact: String,
-    rating: i32,
+    asset_id: u64,
+    min_temperature: i32,
+    max_temperature: i32,
+    interval_minutes: u64,
+    persist_readings: u32,
 ) -> Result<(), String> {
-    ctx.db.suppliers().insert(Supplier {
+    let asset = ctx.db.assets().id().find(asset_id).ok_or("Asset not found")?;
+    if asset.category != COLD_STORAGE_CATEGORY {
+        return Err(format!("{} is not a cold storage asset", asset.name));
+    }
+    if min_temperature > max_temperature {
+        return Err("Minimum temperature must not exceed the maximum".into());
+    }
+    if interval_minutes == 0 {
+        return Err("Reading interval must be positive".into());
+    }
+    if persist_readings == 0 {
+        return Err("Persist readings must be at least 1".into());
+    }
+    let existing = ctx.db.temperature_monitors().asset_id().find(asset_id);
+    let monitor = TemperatureMonitor {
+        asset_id,
+        outlet_id: asset.outlet_id,
+        min_temperature,
+        max_temperature,
+        interval_minutes,
+        persist_readings,
+        consecutive_out_of_range: existing.as_ref().map(|m| m.consecutive_out_of_range).unwrap_or(0),
+        next_due_at: ctx.timestamp.checked_add(minutes(interval_minutes)).unwrap_or(ctx.timestamp),
+        active: true,
+    };
+    if existing.is_some() {
+        ctx.db.temperature_monitors().asset_id().update(monitor);
+    } else {
+        ctx.db.temperature_monitors().insert(monitor);
+    }
+    Ok(())
+}
+
+#[reducer]
+pub fn disable_temperature_monitor(ctx: &ReducerContext, asset_id: u64) -> Result<(), String> {
+    let mut monitor = ctx.db.temperature_monitors().asset_id().find(asset_id).ok_or("Temperature monitor not found")?;
+    monitor.active = false;
+    ctx.db.temperature_monitors().asset_id().update(monitor);
+    resolve_temperature_alert(ctx, asset_id, TemperatureAlertKind::MissedReading);
+    Ok(())
+}
+
+// Out-of-range readings raise an alert; once they persist the asset goes into Maintenance.
+// Disabled monitors take no readings until set_temperature_range turns them back on.
+#[reducer]
+pub fn log_temperature(ctx: &ReducerContext, asset_id: u64, temperature: i32) -> Result<(), String> {
+    let mut monitor = ctx.db.temperature_monitors().asset_id().find(asset_id).ok_or("Temperature monitor not found")?;
+    if !monitor.active {
+        return Err("Temperature monitor is disabled".into());
+    }
+    let in_range = temperature >= monitor.min_temperature && temperature <= monitor.max_temperature;
+    ctx.db.temperature_logs().insert(TemperatureLog {
         id: 0,
-        name,
-        contact,
-        rating,
+        asset_id,
+        outlet_id: monitor.outlet_id,
+        temperature,
+        in_range,
+        recorded_by: ctx.sender,
+        recorded_at: ctx.timestamp,
     });
+    if in_range {
+        monitor.consecutive_out_of_range = 0;
+        resolve_temperature_alert(ctx, asset_id, TemperatureAlertKind::OutOfRange);
+    } else {
+        monitor.consecutive_out_of_range += 1;
+        raise_temperature_alert(ctx, &monitor, TemperatureAlertKind::OutOfRange, Some(temperature));
+        if monitor.consecutive_out_of_range >= monitor.persist_readings {
+            if let Some(mut asset) = ctx.db.assets().id().find(asset_id) {
+                if asset.status == AssetStatus::InUse {
+                    log::warn!(
+                        "Asset {} out of range for {} readings, set to Maintenance",
+                        asset_id,
+                        monitor.consecutive_out_of_range
+                    );
+                    asset.status = AssetStatus::Maintenance;
+                    ctx.db.assets().id().update(asset);
+                }
+            }
+        }
+    }
+    resolve_temperature_alert(ctx, asset_id, TemperatureAlertKind::MissedReading);
+    monitor.next_due_at = ctx.timestamp.checked_add(minutes(monitor.interval_minutes)).unwrap_or(ctx.timestamp);
+    ctx.db.temperature_monitors().asset_id().update(monitor);
     Ok(())
 }
 
 #[reducer]
-pub fn create_purchase_order(
+pub fn acknowledge_temperature_alert(ctx: &ReducerContext, alert_id: u64) -> Result<(), String> {
+    if let Some(mut a) = ctx.db.temperature_alerts().id().find(alert_id) {
+        a.acknowledged = true;
+        a.acknowledged_by = Some(ctx.sender);
+        ctx.db.temperature_alerts().id().update(a);
+        Ok(())
+    } else {
+        Err("Alert not found".into())
+    }
+}
+
+// Records every reading slot that passed its grace period without a log, then moves
+// the monitor on to the next slot that is still open.
+#[reducer]
+pub fn flag_missed_temperature_checks(ctx: &ReducerContext, _schedule: TemperatureCheckSchedule) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("flag_missed_temperature_checks may only be invoked by the scheduler".into());
+    }
+    let monitors: Vec<TemperatureMonitor> = ctx.db.temperature_monitors().iter().filter(|m| m.active).collect();
+    for mut monitor in monitors {
+        let mut missed = 0;
+        loop {
+            let deadline = monitor.next_due_at.checked_add(minutes(TEMPERATURE_GRACE_MINUTES));
+            if deadline.is_none_or(|d| d > ctx.timestamp) {
+                break;
+            }
+            ctx.db.missed_temperature_checks().insert(MissedTemperatureCheck {
+                id: 0,
+                asset_id: monitor.asset_id,
+                outlet_id: monitor.outlet_id,
+                due_at: monitor.next_due_at,
+                flagged_at: ctx.timestamp,
+            });
+            missed += 1;
+            let Some(next) = monitor.next_due_at.checked_add(minutes(monitor.interval_minutes)) else {
+                break;
+            };
+            monitor.next_due_at = next;
+        }
+        if missed > 0 {
+            raise_temperature_alert(ctx, &monitor, TemperatureAlertKind::MissedReading, None);
+            ctx.db.temperature_monitors().asset_id().update(monitor);
+        }
+    }
+    Ok(())
+}
+
+// Users
+
+#[reducer]
+pub fn create_user(
+    ctx: &ReducerContext,
+    username: String,
+    role: UserRole,
+    outlet_id: Option<u64>,
+) -> Result<(), String> {
+    let user_id: Identity = ctx.sender;
+    if ctx.db.users().id().find(user_id).is_some() {
+        return Err("User with this identity already exists".into());
+    }
+    ctx.db.users().insert(User {
+        id: user_id,
+        username,
+        role,
+        outlet_id,
+    });
+    Ok(())
+}
+
+// Sales
+
+// Shared by POS sales and ingested platform orders.
+fn insert_sale(
     ctx: &ReducerContext,
     outlet_id: u64,
-    supplier_id: u64,
-    total: i64,
+    channel: SalesChannel,
+    items: Vec<SaleItemInput>,
+    payment_method: PaymentMethod,
     date: Timestamp,
-    items: Vec<PurchaseOrderItemInput>,
-) -> Result<(), String> {
-    let po = ctx.db.purchase_orders().insert(PurchaseOrder {
+) -> Result<Sale, String> {
+    if items.is_empty() {
+        return Err("Sale must have at least one item".into());
+    }
+    let mut total: i64 = 0;
+    for it in &items {
+        if it.quantity <= 0 {
+            return Err(format!("Quantity for product {} must be positive", it.product_id));
+        }
+        if it.price < 0 {
+            return Err(format!("Price for product {} must not be negative", it.product_id));
+        }
+        if !product_sold_at(ctx, it.product_id, outlet_id) {
+            return Err(format!("Product {} is not on the menu at this outlet", it.product_id));
+        }
+        if let Some(a) = find_availability(ctx, outlet_id, it.product_id) {
+            match a.status {
+                AvailabilityStatus::SoldOut => {
+                    let reason = a.manual_reason.as_ref().unwrap_or(&a.reason);
+                    return Err(format!("Product {} is sold out ({})", it.product_id, reason));
+                }
+                AvailabilityStatus::Low => {
+                    let reason = a.manual_reason.as_ref().unwrap_or(&a.reason);
+                    log::warn!("Product {} is running low at outlet {}: {}", it.product_id, outlet_id, reason);
+                }
+                AvailabilityStatus::Available => {}
+            }
+        }
+        total = total.saturating_add(it.price.saturating_mul(it.quantity as i64));
+    }
+    let mut sale = ctx.db.sales().insert(Sale {
         id: 0,
         outlet_id,
-        supplier_id,
+        channel,
         total,
-        status: POStatus::Created,
+        cogs: 0,
+        discount: 0,
+        payment_method,
         date,
     });
+    let reference = format!("sale:{}", sale.id);
     for it in items {
-        ctx.db.purchase_order_items().insert(PurchaseOrderItem {
+        ctx.db.sale_items().insert(SaleItem {
             id: 0,
-            purchase_order_id: po.id,
-            ingredient_id: it.ingredient_id,
+            sale_id: sale.id,
+            product_id: it.product_id,
             quantity: it.quantity,
             price: it.price,
         });
+        let lines: Vec<Recipe> = ctx.db.recipes().recipe_product_idx().filter(it.product_id).collect();
+        for r in lines {
+            let st = require_outlet_stock(ctx, r.ingredient_id, outlet_id)?;
+            let used = r.quantity.saturating_mul(it.quantity as i64);
+            let movement = post_stock_movement(ctx, st.id, -used, StockMovementType::Sale, &reference, "", None)?;
+            sale.cogs = sale.cogs.saturating_sub(movement.value);
+        }
+    }
+    ctx.db.sales().id().update(sale.clone());
+    Ok(sale)
+}
+
+#[reducer]
+pub fn record_sale(
+    ctx: &ReducerContext,
+    outlet_id: u64,
+    channel: SalesChannel,
+    items: Vec<SaleItemInput>,
+    payment_method: PaymentMethod,
+    date: Timestamp,
+    vouchers: Vec<SaleVoucherInput>,
+) -> Result<(), String> {
+    if matches!(channel, SalesChannel::Delivery(_)) {
+        return Err("Delivery orders must be ingested through ingest_external_order".into());
+    }
+    if payment_method == PaymentMethod::Voucher && !vouchers.iter().any(|v| v.usage == VoucherUsage::Payment) {
+        return Err("Voucher payment needs at least one payment voucher".into());
+    }
+    for it in &items {
+        let menu_price = effective_price(ctx, outlet_id, it.product_id)
+            .ok_or_else(|| format!("Product {} is not on the menu at this outlet", it.product_id))?;
+        if it.price != menu_price {
+            return Err(format!("Price for product {} does not match the menu price {}", it.product_id, menu_price));
+        }
     }
+    let mut sale = insert_sale(ctx, outlet_id, channel, items, payment_method, date)?;
+    // Discounts are applied before payments so payment vouchers are capped at the discounted total.
+    let (discounts, payments): (Vec<_>, Vec<_>) =
+        vouchers.into_iter().partition(|v| v.usage == VoucherUsage::Discount);
+    let mut paid: i64 = 0;
+    for v in discounts.into_iter().chain(payments) {
+        let due = sale.total.saturating_sub(paid);
+        let amount = redeem_voucher(ctx, &v, &sale, due)?;
+        if v.usage == VoucherUsage::Discount {
+            sale.discount = sale.discount.saturating_add(amount);
+            sale.total = sale.total.saturating_sub(amount);
+        } else {
+            paid = paid.saturating_add(amount);
+        }
+    }
+    // A sale paid by voucher must be covered by its vouchers in full.
+    if sale.payment_method == PaymentMethod::Voucher && paid < sale.total {
+        return Err(format!("Vouchers cover {} of the {} due; pay the rest with another method", paid, sale.total));
+    }
+    ctx.db.sales().id().update(sale);
     Ok(())
 }
 
-#[reducer]
-pub fn approve_purchase_order(ctx: &ReducerContext, po_id: u64) -> Result<(), String> {
-    if let Some(mut po) = ctx.db.purchase_orders().id().find(po_id) {
-        po.status = POStatus::Ordered;
-        ctx.db.purchase_orders().id().update(po);
-        Ok(())
-    } else {
-        Err("Purchase order not found".into())
-    }
-}
+// Delivery platforms
 
-#[reducer]
-pub fn reject_purchase_order(ctx: &ReducerContext, po_id: u64) -> Result<(), String> {
-    if let Some(mut po) = ctx.db.purchase_orders().id().find(po_id) {
-        po.status = POStatus::Cancelled;
-        ctx.db.purchase_orders().id().update(po);
-        Ok(())
-    } else {
-        Err("Purchase order not found".into())
+// Commission charged on orders fed in by simulate_external_order, in basis points
+const SIMULATED_COMMISSION_BPS: i64 = 2_000;
+
+fn platform_name(platform: &DeliveryPlatform) -> &'static str {
+    match platform {
+        DeliveryPlatform::GoFood => "gofood",
+        DeliveryPlatform::GrabFood => "grabfood",
+        DeliveryPlatform::ShopeeFood => "shopeefood",
     }
 }
 
-// Distribution
+fn find_platform_mapping(
+    ctx: &ReducerContext,
+    platform: &DeliveryPlatform,
+    outlet_id: u64,
+    external_item_id: &str,
+) -> Option<PlatformProductMapping> {
+    ctx.db
+        .platform_product_mappings()
+        .ppm_outlet_idx()
+        .filter(outlet_id)
+        .find(|m| &m.platform == platform && m.external_item_id == external_item_id)
+}
 
 #[reducer]
-pub fn request_distribution(
+pub fn map_platform_product(
     ctx: &ReducerContext,
-    from_outlet_id: u64,
-    to_outlet_id: u64,
-    ingredient_id: u64,
-    quantity: i64,
-    date: Timestamp,
+    platform: DeliveryPlatform,
+    outlet_id: u64,
+    external_item_id: String,
+    product_id: u64,
 ) -> Result<(), String> {
-    if from_outlet_id == to_outlet_id {
-        return Err("from_outlet_id and to_outlet_id must differ".into());
+    if ctx.db.products().id().find(product_id).is_none() {
+        return Err("Product not found".into());
+    }
+    if let Some(mut m) = find_platform_mapping(ctx, &platform, outlet_id, &external_item_id) {
+        m.product_id = product_id;
+        ctx.db.platform_product_mappings().id().update(m);
+    } else {
+        ctx.db.platform_product_mappings().insert(PlatformProductMapping {
+            id: 0,
+            platform,
+            outlet_id,
+            external_item_id,
+            product_id,
+        });
     }
-    ctx.db.distributions().insert(Distribution {
-        id: 0,
-        from_outlet_id,
-        to_outlet_id,
-        ingredient_id,
-        quantity,
-        status: DistributionStatus::Pending,
-        date,
-    });
     Ok(())
 }
 
 #[reducer]
-pub fn mark_distribution_delivered(ctx: &ReducerContext, distribution_id: u64) -> Result<(), String> {
-    if let Some(mut d) = ctx.db.distributions().id().find(distribution_id) {
-        d.status = DistributionStatus::Delivered;
-        ctx.db.distributions().id().update(d);
+pub fn delete_platform_mapping(ctx: &ReducerContext, mapping_id: u64) -> Result<(), String> {
+    if ctx.db.platform_product_mappings().id().delete(mapping_id) {
         Ok(())
     } else {
-        Err("Distribution not found".into())
+        Err("Mapping not found".into())
     }
 }
 
-// Operations
-
 #[reducer]
-pub fn create_daily_checklist(
-    ctx: &ReducerContext,
-    outlet_id: u64,
-    checklist_name: String,
-    date: Timestamp,
-) -> Result<(), String> {
-    ctx.db.daily_checklists().insert(DailyChecklist {
+pub fn ingest_external_order(ctx: &ReducerContext, order: ExternalOrderInput) -> Result<(), String> {
+    let external_key = format!("{}:{}", platform_name(&order.platform), order.external_order_id);
+    if ctx.db.external_orders().external_key().find(&external_key).is_some() {
+        log::info!("External order {} already ingested, skipping", external_key);
+        return Ok(());
+    }
+    if ctx.db.outlets().id().find(order.outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    let mut items = Vec::with_capacity(order.items.len());
+    let mut item_total: i64 = 0;
+    for it in &order.items {
+        if it.quantity <= 0 {
+            return Err(format!("Quantity for item {} must be positive", it.external_item_id));
+        }
+        let mapping = find_platform_mapping(ctx, &order.platform, order.outlet_id, &it.external_item_id)
+            .ok_or_else(|| format!("No product mapped for {:?} item {}", order.platform, it.external_item_id))?;
+        item_total = item_total.saturating_add(it.price.saturating_mul(it.quantity as i64));
+        items.push(SaleItemInput {
+            product_id: mapping.product_id,
+            quantity: it.quantity,
+            price: it.price,
+        });
+    }
+    if order.platform_total != item_total {
+        return Err(format!("Platform total {} does not match the item total {}", order.platform_total, item_total));
+    }
+    if order.commission < 0 || order.commission > order.platform_total {
+        return Err("Commission must be between zero and the platform total".into());
+    }
+    let sale = insert_sale(
+        ctx,
+        order.outlet_id,
+        SalesChannel::Delivery(order.platform.clone()),
+        items,
+        PaymentMethod::Transfer,
+        order.date,
+    )?;
+    ctx.db.external_orders().insert(ExternalOrder {
         id: 0,
-        outlet_id,
-        checklist_name,
-        is_completed: false,
-        date,
+        external_key,
+        platform: order.platform,
+        external_order_id: order.external_order_id,
+        outlet_id: order.outlet_id,
+        sale_id: sale.id,
+        platform_total: order.platform_total,
+        commission: order.commission,
+        net_receivable: order.platform_total - order.commission,
+        received_at: ctx.timestamp,
     });
     Ok(())
 }
 
+// Local stand-in for the platforms: builds an order from the outlet's mappings for the
+// platform at menu prices and ingests it like a real payload.
 #[reducer]
-pub fn update_checklist_status(
+pub fn simulate_external_order(
     ctx: &ReducerContext,
-    checklist_id: u64,
-    is_completed: bool,
+    platform: DeliveryPlatform,
+    outlet_id: u64,
+    item_count: u32,
 ) -> Result<(), String> {
-    if let Some(mut c) = ctx.db.daily_checklists().id().find(checklist_id) {
-        c.is_completed = is_completed;
-        ctx.db.daily_checklists().id().update(c);
-        Ok(())
-    } else {
-        Err("Checklist not found".into())
+    if item_count == 0 {
+        return Err("Item count must be positive".into());
+    }
+    let mappings: Vec<(PlatformProductMapping, i64)> = ctx
+        .db
+        .platform_product_mappings()
+        .ppm_outlet_idx()
+        .filter(outlet_id)
+        .filter(|m| m.platform == platform)
+        .filter_map(|m| effective_price(ctx, outlet_id, m.product_id).map(|price| (m, price)))
+        .collect();
+    if mappings.is_empty() {
+        return Err(format!("No {:?} items are mapped at this outlet", platform));
+    }
+    let mut items = Vec::with_capacity(item_count as usize);
+    for _ in 0..item_count {
+        let (m, price) = &mappings[(ctx.random::<u64>() % mappings.len() as u64) as usize];
+        items.push(ExternalOrderItemInput {
+            external_item_id: m.external_item_id.clone(),
+            quantity: 1,
+            price: *price,
+        });
     }
+    let platform_total: i64 = items.iter().map(|it| it.price).sum();
+    let external_order_id = format!("SIM-{:016X}", ctx.random::<u64>());
+    ingest_external_order(
+        ctx,
+        ExternalOrderInput {
+            platform,
+            external_order_id,
+            outlet_id,
+            items,
+            platform_total,
+            commission: platform_total * SIMULATED_COMMISSION_BPS / 10_000,
+            date: ctx.timestamp,
+        },
+    )
 }
 
-#[reducer]
-pub fn open_shift(
+// Vouchers
+
+const VOUCHER_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const VOUCHER_CODE_LEN: usize = 10;
+
+fn generate_voucher_code(ctx: &ReducerContext, prefix: &str) -> String {
+    loop {
+        let mut n: u64 = ctx.random();
+        let mut code = format!("{}-", prefix);
+        for _ in 0..VOUCHER_CODE_LEN {
+            code.push(VOUCHER_CODE_ALPHABET[(n % 32) as usize] as char);
+            n /= 32;
+        }
+        if ctx.db.vouchers().code().find(&code).is_none() {
+            return code;
+        }
+    }
+}
+
+// Redeems a voucher against a sale and returns the amount applied, in cents.
+// Runs inside the caller's transaction, so a code can never be spent twice.
+fn redeem_voucher(
     ctx: &ReducerContext,
-    outlet_id: u64,
-    employee_id: u64,
-    shift_start: Timestamp,
-    initial_cash: i64,
-) -> Result<(), String> {
-    ctx.db.shift_reports().insert(ShiftReport {
+    input: &SaleVoucherInput,
+    sale: &Sale,
+    due: i64,
+) -> Result<i64, String> {
+    let mut voucher = ctx
+        .db
+        .vouchers()
+        .code()
+        .find(&input.code)
+        .ok_or_else(|| format!("Voucher {} not found", input.code))?;
+    if voucher.status != VoucherStatus::Active {
+        return Err(format!("Voucher {} is no longer active", input.code));
+    }
+    if voucher.expires_at < ctx.timestamp {
+        return Err(format!("Voucher {} has expired", input.code));
+    }
+    if due <= 0 {
+        return Err("Sale has no amount left to cover with a voucher".into());
+    }
+    let amount = match voucher.kind {
+        VoucherKind::SingleUse => voucher.balance.min(due),
+        VoucherKind::Balance => {
+            if input.amount <= 0 {
+                return Err("Voucher amount must be positive".into());
+            }
+            if input.amount > voucher.balance {
+                return Err(format!("Voucher {} has insufficient balance", input.code));
+            }
+            if input.amount > due {
+                return Err("Voucher amount exceeds amount due".into());
+            }
+            input.amount
+        }
+    };
+    // A SingleUse voucher is spent in one go and gives no change: whatever exceeds the
+    // amount due is forfeited and recorded on the redemption.
+    let forfeited = match voucher.kind {
+        VoucherKind::SingleUse => voucher.balance - amount,
+        VoucherKind::Balance => 0,
+    };
+    voucher.balance = match voucher.kind {
+        VoucherKind::SingleUse => 0,
+        VoucherKind::Balance => voucher.balance - amount,
+    };
+    if voucher.balance == 0 {
+        voucher.status = VoucherStatus::Redeemed;
+    }
+    ctx.db.voucher_redemptions().insert(VoucherRedemption {
         id: 0,
-        outlet_id,
-        employee_id,
-        shift_start,
-        shift_end: shift_start,
-        initial_cash,
-        final_cash: 0,
-        status: ShiftStatus::Open,
+        voucher_id: voucher.id,
+        sale_id: sale.id,
+        outlet_id: sale.outlet_id,
+        usage: input.usage.clone(),
+        amount,
+        forfeited,
+        date: sale.date,
     });
-    Ok(())
+    ctx.db.vouchers().id().update(voucher);
+    Ok(amount)
 }
 
-// HR
-
 #[reducer]
-pub fn add_candidate(
+pub fn issue_voucher_batch(
     ctx: &ReducerContext,
     name: String,
-    position: String,
-    phone: String,
-    email: String,
+    kind: VoucherKind,
+    face_value: i64,
+    quantity: u32,
+    expires_at: Timestamp,
+    code_prefix: String,
 ) -> Result<(), String> {
-    ctx.db.candidates().insert(Candidate {
+    if face_value <= 0 {
+        return Err("Face value must be positive".into());
+    }
+    if quantity == 0 {
+        return Err("Batch must contain at least one voucher".into());
+    }
+    if expires_at <= ctx.timestamp {
+        return Err("Expiry must be in the future".into());
+    }
+    let batch = ctx.db.voucher_batches().insert(VoucherBatch {
         id: 0,
         name,
-        position,
-        phone,
-        email,
-        status: CandidateStatus::Applied,
+        kind: kind.clone(),
+        face_value,
+        quantity,
+        expires_at,
+        created_at: ctx.timestamp,
     });
+    let prefix = code_prefix.trim().to_uppercase();
+    for _ in 0..quantity {
+        let code = generate_voucher_code(ctx, &prefix);
+        ctx.db.vouchers().insert(Voucher {
+            id: 0,
+            code,
+            batch_id: batch.id,
+            kind: kind.clone(),
+            face_value,
+            balance: face_value,
+            expires_at,
+            status: VoucherStatus::Active,
+        });
+    }
     Ok(())
 }
 
 #[reducer]
-pub fn update_candidate_status(
-    ctx: &ReducerContext,
-    candidate_id: u64,
-    status: CandidateStatus,
-) -> Result<(), String> {
-    if let Some(mut c) = ctx.db.candidates().id().find(candidate_id) {
-        c.status = status.clone();
-        ctx.db.candidates().id().update(c);
-        Ok(())
-    } else {
-        Err("Candidate not found".into())
+pub fn void_voucher(ctx: &ReducerContext, code: String) -> Result<(), String> {
+    let mut v = ctx.db.vouchers().code().find(&code).ok_or("Voucher not found")?;
+    if v.status != VoucherStatus::Active {
+        return Err(format!("Voucher {} is {:?} and cannot be voided", code, v.status));
     }
+    v.status = VoucherStatus::Void;
+    ctx.db.vouchers().code().update(v);
+    Ok(())
 }
 
-// Promotions
+// Tables and reservations
 
-#[reducer]
-pub fn create_promotion(
-    ctx: &ReducerContext,
-    name: String,
-    discount_type: DiscountType,
-    discount_value: i64,
-    start_date: Timestamp,
-    end_date: Timestamp,
-    status: PromotionStatus,
-) -> Result<(), String> {
-    ctx.db.promotions().insert(Promotion {
-        id: 0,
-        name,
-        discount_type,
-        discount_value,
-        start_date,
-        end_date,
-        status,
-    });
-    Ok(())
+// Minutes a booking is held past its start time before it is released as a no-show.
+const RESERVATION_GRACE_MINUTES: u64 = 15;
+// How long a walk-in is assumed to occupy a table when checking upcoming bookings.
+const WALK_IN_DURATION_MINUTES: u64 = 90;
+
+fn minutes(m: u64) -> TimeDuration {
+    TimeDuration::from_duration(Duration::from_secs(m * 60))
 }
 
-// Assets
+fn find_table_conflict(
+    ctx: &ReducerContext,
+    table_id: u64,
+    start: Timestamp,
+    end: Timestamp,
+    ignore_id: Option<u64>,
+) -> Option<Reservation> {
+    ctx.db.reservations().res_table_idx().filter(table_id).find(|r| {
+        Some(r.id) != ignore_id
+            && matches!(r.status, ReservationStatus::Booked | ReservationStatus::Seated)
+            && r.start_time < end
+            && start < r.end_time
+    })
+}
 
 #[reducer]
-pub fn add_asset(
+pub fn add_dining_table(
     ctx: &ReducerContext,
     outlet_id: u64,
-    name: String,
-    category: String,
-    last_maintenance: Timestamp,
+    number: String,
+    capacity: u32,
+    area: String,
 ) -> Result<(), String> {
-    ctx.db.assets().insert(Asset {
+    if ctx.db.outlets().id().find(outlet_id).is_none() {
+        return Err("Outlet not found".into());
+    }
+    if capacity == 0 {
+        return Err("Capacity must be at least 1".into());
+    }
+    if ctx.db.dining_tables().table_outlet_idx().filter(outlet_id).any(|t| t.number == number) {
+        return Err(format!("Table {} already exists at this outlet", number));
+    }
+    ctx.db.dining_tables().insert(DiningTable {
         id: 0,
         outlet_id,
-        name,
-        category,
-        status: AssetStatus::InUse,
-        last_maintenance,
+        number,
+        capacity,
+        area,
+        status: TableStatus::Available,
     });
     Ok(())
 }
 
 #[reducer]
-pub fn update_asset_status(
-    ctx: &ReducerContext,
-    asset_id: u64,
-    status: AssetStatus,
-    last_maintenance: Timestamp,
-) -> Result<(), String> {
-    if let Some(mut a) = ctx.db.assets().id().find(asset_id) {
-        a.status = status.clone();
-        a.last_maintenance = last_maintenance;
-        ctx.db.assets().id().update(a);
+pub fn update_table_status(ctx: &ReducerContext, table_id: u64, status: TableStatus) -> Result<(), String> {
+    if let Some(mut t) = ctx.db.dining_tables().id().find(table_id) {
+        t.status = status;
+        ctx.db.dining_tables().id().update(t);
         Ok(())
     } else {
-        Err("Asset not found".into())
+        Err("Table not found".into())
     }
 }
 
-// Users
-
 #[reducer]
-pub fn create_user(
+pub fn create_reservation(
     ctx: &ReducerContext,
-    username: String,
-    role: UserRole,
-    outlet_id: Option<u64>,
+    table_id: u64,
+    customer_name: String,
+    customer_phone: String,
+    party_size: u32,
+    start_time: Timestamp,
+    end_time: Timestamp,
 ) -> Result<(), String> {
-    let user_id: Identity = ctx.sender;
-    if ctx.db.users().id().find(&user_id).is_some() {
-        return Err("User with this identity already exists".into());
+    let table = ctx.db.dining_tables().id().find(table_id).ok_or("Table not found")?;
+    if table.status == TableStatus::OutOfService {
+        return Err("Table is out of service".into());
     }
-    ctx.db.users().insert(User {
-        id: user_id,
-        username,
-        role,
-        outlet_id,
+    if party_size == 0 || party_size > table.capacity {
+        return Err(format!("Party size must be between 1 and {}", table.capacity));
+    }
+    if end_time <= start_time {
+        return Err("end_time must be after start_time".into());
+    }
+    if let Some(c) = find_table_conflict(ctx, table_id, start_time, end_time, None) {
+        return Err(format!("Table {} is already booked (reservation {})", table.number, c.id));
+    }
+    ctx.db.reservations().insert(Reservation {
+        id: 0,
+        outlet_id: table.outlet_id,
+        table_id,
+        customer_name,
+        customer_phone,
+        party_size,
+        start_time,
+        end_time,
+        status: ReservationStatus::Booked,
+        created_at: ctx.timestamp,
     });
     Ok(())
 }
 
-// Sales
+#[reducer]
+pub fn cancel_reservation(ctx: &ReducerContext, reservation_id: u64) -> Result<(), String> {
+    let mut r = ctx.db.reservations().id().find(reservation_id).ok_or("Reservation not found")?;
+    if r.status != ReservationStatus::Booked {
+        return Err("Only booked reservations can be cancelled".into());
+    }
+    r.status = ReservationStatus::Cancelled;
+    ctx.db.reservations().id().update(r);
+    Ok(())
+}
 
 #[reducer]
-pub fn record_sale(
+pub fn seat_reservation(ctx: &ReducerContext, reservation_id: u64) -> Result<(), String> {
+    let mut r = ctx.db.reservations().id().find(reservation_id).ok_or("Reservation not found")?;
+    if r.status != ReservationStatus::Booked {
+        return Err("Reservation is not in Booked status".into());
+    }
+    let mut table = ctx.db.dining_tables().id().find(r.table_id).ok_or("Table not found")?;
+    if table.status != TableStatus::Available {
+        return Err(format!("Table {} is not available", table.number));
+    }
+    r.status = ReservationStatus::Seated;
+    table.status = TableStatus::Occupied;
+    ctx.db.reservations().id().update(r);
+    ctx.db.dining_tables().id().update(table);
+    Ok(())
+}
+
+#[reducer]
+pub fn seat_walk_in(
     ctx: &ReducerContext,
-    outlet_id: u64,
-    items: Vec<SaleItemInput>,
-    payment_method: PaymentMethod,
-    date: Timestamp,
+    table_id: u64,
+    customer_name: String,
+    party_size: u32,
 ) -> Result<(), String> {
-    if items.is_empty() {
-        return Err("Sale must have at least one item".into());
+    let mut table = ctx.db.dining_tables().id().find(table_id).ok_or("Table not found")?;
+    if table.status != TableStatus::Available {
+        return Err(format!("Table {} is not available", table.number));
     }
-    let mut total: i64 = 0;
-    for it in &items {
-        total = total.saturating_add(it.price.saturating_mul(it.quantity as i64));
+    if party_size == 0 || party_size > table.capacity {
+        return Err(format!("Party size must be between 1 and {}", table.capacity));
     }
-    let sale = ctx.db.sales().insert(Sale {
+    let start_time = ctx.timestamp;
+    let end_time = start_time.checked_add(minutes(WALK_IN_DURATION_MINUTES)).unwrap_or(start_time);
+    if let Some(c) = find_table_conflict(ctx, table_id, start_time, end_time, None) {
+        return Err(format!("Table {} is booked soon (reservation {})", table.number, c.id));
+    }
+    ctx.db.reservations().insert(Reservation {
         id: 0,
-        outlet_id,
-        total,
-        payment_method,
-        date,
+        outlet_id: table.outlet_id,
+        table_id,
+        customer_name,
+        customer_phone: String::new(),
+        party_size,
+        start_time,
+        end_time,
+        status: ReservationStatus::Seated,
+        created_at: ctx.timestamp,
     });
-    for it in items {
-        ctx.db.sale_items().insert(SaleItem {
-            id: 0,
-            sale_id: sale.id,
-            product_id: it.product_id,
-            quantity: it.quantity,
-            price: it.price,
-        });
+    table.status = TableStatus::Occupied;
+    ctx.db.dining_tables().id().update(table);
+    Ok(())
+}
+
+#[reducer]
+pub fn release_table(ctx: &ReducerContext, table_id: u64) -> Result<(), String> {
+    let mut table = ctx.db.dining_tables().id().find(table_id).ok_or("Table not found")?;
+    let seated: Vec<Reservation> = ctx
+        .db
+        .reservations()
+        .res_table_idx()
+        .filter(table_id)
+        .filter(|r| r.status == ReservationStatus::Seated)
+        .collect();
+    for mut r in seated {
+        r.status = ReservationStatus::Completed;
+        ctx.db.reservations().id().update(r);
+    }
+    table.status = TableStatus::Available;
+    ctx.db.dining_tables().id().update(table);
+    Ok(())
+}
+
+#[reducer]
+pub fn release_no_show_reservations(
+    ctx: &ReducerContext,
+    _schedule: ReservationReleaseSchedule,
+) -> Result<(), String> {
+    if ctx.sender != ctx.identity() {
+        return Err("release_no_show_reservations may only be invoked by the scheduler".into());
+    }
+    let grace = minutes(RESERVATION_GRACE_MINUTES);
+    let expired: Vec<Reservation> = ctx
+        .db
+        .reservations()
+        .iter()
+        .filter(|r| {
+            r.status == ReservationStatus::Booked
+                && r.start_time.checked_add(grace).is_some_and(|t| t < ctx.timestamp)
+        })
+        .collect();
+    for mut r in expired {
+        log::info!("Releasing no-show reservation {} at outlet {}", r.id, r.outlet_id);
+        r.status = ReservationStatus::NoShow;
+        ctx.db.reservations().id().update(r);
     }
     Ok(())
-}
\ No newline at end of file
+}
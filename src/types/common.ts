@@ -65,9 +65,26 @@ export enum UserRole {
 export enum PaymentMethod {
   Cash = 'Cash',
   Card = 'Card',
-  Transfer = 'Transfer'
+  Transfer = 'Transfer',
+  Voucher = 'Voucher'
 }
 
+export enum VoucherUsage {
+  Payment = 'Payment',
+  Discount = 'Discount'
+}
+
+export enum DeliveryPlatform {
+  GoFood = 'GoFood',
+  GrabFood = 'GrabFood',
+  ShopeeFood = 'ShopeeFood'
+}
+
+export type SalesChannel =
+  | { tag: 'DineIn' }
+  | { tag: 'Takeaway' }
+  | { tag: 'Delivery'; value: DeliveryPlatform };
+
 export enum PromotionStatus {
   Active = 'Active',
   Inactive = 'Inactive',
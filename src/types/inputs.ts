@@ -1,11 +1,20 @@
+import type { VoucherUsage } from './common';
+
 export interface PurchaseOrderItemInput {
   ingredientId: bigint;
+  unitId: bigint;
   quantity: bigint;
-  price: bigint;
+  price?: bigint;
 }
 
 export interface SaleItemInput {
   productId: bigint;
   quantity: bigint;
   price: bigint;
+}
+
+export interface SaleVoucherInput {
+  code: string;
+  usage: VoucherUsage;
+  amount: bigint;
 }
\ No newline at end of file
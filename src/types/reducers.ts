@@ -8,10 +8,11 @@ import type {
   EmploymentStatus,
   OutletStatus,
   PaymentMethod,
+  SalesChannel,
   UserRole
 } from './common';
 
-import type { PurchaseOrderItemInput, SaleItemInput } from './inputs';
+import type { PurchaseOrderItemInput, SaleItemInput, SaleVoucherInput } from './inputs';
 
 export interface RemoteReducers {
   createPromotion(
@@ -38,7 +39,6 @@ export interface RemoteReducers {
   createPurchaseOrder(
     outletId: bigint,
     supplierId: bigint,
-    total: bigint,
     date: __Timestamp,
     items: PurchaseOrderItemInput[]
   ): void;
@@ -69,9 +69,11 @@ export interface RemoteReducers {
 
   recordSale(
     outletId: bigint,
+    channel: SalesChannel,
     items: SaleItemInput[],
     paymentMethod: PaymentMethod,
-    date: __Timestamp
+    date: __Timestamp,
+    vouchers: SaleVoucherInput[]
   ): void;
 
   createUser(